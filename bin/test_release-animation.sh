@@ -1,7 +1,6 @@
 # Build for release
 cargo build -r
 
-./target/release/raytracer render examples/scenes/lots_of_objects/scene.rrtscene animation 0 24
+./target/release/cli render examples/scenes/lots_of_objects/scene.rrtscene --animation 0 24 --video output/video.gif
 
-ffmpeg -framerate 24 -i examples/scenes/lots_of_objects/output/frame-%01d.png examples/scenes/lots_of_objects/output/video.mp4
-ffmpeg -framerate 24 -i examples/scenes/lots_of_objects/output/frame-%01d.png examples/scenes/lots_of_objects/output/video.gif
\ No newline at end of file
+ffmpeg -framerate 24 -start_number 0 -i examples/scenes/lots_of_objects/output/frame-%04d.png examples/scenes/lots_of_objects/output/video.mp4
\ No newline at end of file
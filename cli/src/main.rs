@@ -1,81 +1,346 @@
-use engine::scene::scene::{RenderType, Scene};
-use std::{env, fmt::Error};
+mod progress;
 
+use std::{io, path::Path, process::ExitCode, sync::atomic::Ordering};
+
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use engine::{
+    camera::CameraConfig,
+    checkpoint::Checkpoint,
+    projection::Projection,
+    scene::{
+        frames::FrameSpec,
+        output::OutputTemplate,
+        scene::{save_film, RenderOverrides, RenderType, Scene, SCENE_FILE_EXTENSION},
+    },
+    tile::Tile,
+    video::{VideoConfig, VideoFormat},
+};
+use progress::{CliProgress, ProgressMode};
+
+const CHECKPOINT_FILE_EXTENSION: &str = ".checkpoint";
+
+/// Renders and manages ray tracer scene files.
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
 enum Command {
-    Render(String, RenderType),
-    Editor(String),
+    /// Render frames of a scene into its output directory
+    Render(Box<RenderArgs>),
+    /// Check that a scene file loads
+    Check { scene_file: String },
+    /// Create a scene file with the example scene
+    Init {
+        scene_file: String,
+        /// Overwrite the scene file if it exists
+        #[arg(long)]
+        force: bool,
+    },
+    /// Print an overview of a scene file
+    Info { scene_file: String },
+    /// Turn a checkpoint into an image, or rewrite a scene file in the current format
+    Convert { input: String, output: String },
+    /// Print the completion script for a shell
+    Completions { shell: Shell },
+}
+
+#[derive(Args)]
+struct RenderArgs {
+    /// Scene file to render, the example scene is created if it doesn't exist
+    scene_file: String,
+    /// Frame to render
+    #[arg(long, default_value_t = 0, conflicts_with_all = ["animation", "frames"])]
+    frame: u32,
+    /// Render FRAMES frames starting at START
+    #[arg(long, num_args = 2, value_names = ["START", "FRAMES"], conflicts_with = "frames")]
+    animation: Option<Vec<u32>>,
+    /// Frames to render like 1-100, every fifth one with 1-100x5, or a list like 1,5,9
+    #[arg(long, value_name = "SPEC")]
+    frames: Option<FrameSpec>,
+    /// Continue frames from the checkpoints left behind by an interrupted render
+    #[arg(long)]
+    resume: bool,
+    /// Seed all random numbers of the render are derived from instead of the one of the scene
+    /// file
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Render with the camera of this name
+    #[arg(long, conflicts_with = "all_cameras")]
+    camera: Option<String>,
+    /// Render with every camera of the scene, one after the other
+    #[arg(long)]
+    all_cameras: bool,
+    /// Image width in pixels
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    width: Option<u32>,
+    /// Width of the image divided by its height, the height follows from the width
+    #[arg(long, value_parser = parse_positive)]
+    aspect_ratio: Option<f64>,
+    /// Samples per pixel
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    samples: Option<u32>,
+    /// Maximum number of bounces
+    #[arg(long)]
+    max_depth: Option<u32>,
+    /// Only render this part of the image, in pixels of the full image
+    #[arg(long, value_name = "X0,Y0,X1,Y1", value_parser = parse_region)]
+    region: Option<Tile>,
+    /// Write only the region instead of the full image
+    #[arg(long, requires = "region")]
+    crop: bool,
+    /// Path of the images relative to the scene file, with {frame}, {frame:4} for padding,
+    /// {camera} and {pass} replaced per image
+    #[arg(long, value_name = "TEMPLATE")]
+    output: Option<OutputTemplate>,
+    /// Assemble the frames of an animation into a .gif, .png or .webp, relative to the scene
+    /// file like the output
+    #[arg(long, value_name = "TEMPLATE", value_parser = parse_video)]
+    video: Option<OutputTemplate>,
+    /// Only print warnings and errors
+    #[arg(long, conflicts_with = "json_progress")]
+    quiet: bool,
+    /// Print the progress as one JSON object per line instead of a progress bar
+    #[arg(long)]
+    json_progress: bool,
+}
+
+fn parse_positive(value: &str) -> Result<f64, String> {
+    let number = value.parse::<f64>().map_err(|error| error.to_string())?;
+    if !(number > 0. && number.is_finite()) {
+        return Err("expected a number greater than 0".to_string());
+    }
+    Ok(number)
+}
+
+fn parse_region(value: &str) -> Result<Tile, String> {
+    let coordinates = value
+        .split(',')
+        .map(|coordinate| coordinate.trim().parse::<u32>())
+        .collect::<Result<Vec<u32>, _>>()
+        .map_err(|error| error.to_string())?;
+    let [x0, y0, x1, y1] = coordinates[..] else {
+        return Err("expected four coordinates x0,y0,x1,y1".to_string());
+    };
+    if x0 >= x1 || y0 >= y1 {
+        return Err("the region is empty".to_string());
+    }
+
+    Ok(Tile { x0, y0, x1, y1 })
 }
 
-impl Command {
-    fn from_args(args: Vec<String>) -> Result<Command, Error> {
-        match args[1].as_str() {
-            "render" => {
-                if args.len() < 4 {
-                    panic!("Please provide a scene file and a render type.");
-                }
-                let scene_file = args[2].clone();
-                let render_type: RenderType = match args[3].as_str() {
-                    "frame" => {
-                        let frame = if args.len() == 5 {
-                            args[4].parse::<u32>().unwrap()
-                        } else {
-                            0
-                        };
-                        RenderType::SingleFrame(frame)
-                    }
-                    "animation" => {
-                        if args.len() != 6 {
-                            panic!("Please provide a start frame and the number of frames.");
-                        }
-                        let start_frame = args[4].parse::<u32>().unwrap();
-                        let frames = args[5].parse::<u32>().unwrap();
-                        RenderType::Animation(start_frame, frames)
-                    }
-                    _ => {
-                        panic!("Please provide a valid render type.");
-                    }
-                };
-                return Ok(Command::Render(scene_file, render_type));
-            }
-            "editor" => {
-                if args.len() != 3 {
-                    panic!("Please provide a scene file.");
-                }
-
-                let scene_file = args[2].clone();
-
-                return Ok(Command::Editor(scene_file));
-            }
-            _ => panic!("Please provide a valid command."),
+fn parse_video(value: &str) -> Result<OutputTemplate, String> {
+    VideoFormat::from_path(value)?;
+    value.parse()
+}
+
+fn render(args: RenderArgs) -> Result<(), String> {
+    let progress = CliProgress::new(match (args.quiet, args.json_progress) {
+        (true, _) => ProgressMode::Quiet,
+        (_, true) => ProgressMode::Json,
+        _ => ProgressMode::Bar,
+    });
+
+    let exists = Path::new(&args.scene_file).exists();
+    let mut scene = Scene::load_or_create(&args.scene_file, &progress)?;
+    if !exists {
+        // Only a missing scene file is written, a render never changes an existing one
+        scene.save_config(&args.scene_file)?;
+    }
+    scene.set_resume(args.resume);
+    scene.set_overrides(RenderOverrides {
+        image_width: args.width,
+        aspect_ratio: args.aspect_ratio,
+        samples_per_pixel: args.samples,
+        max_depth: args.max_depth,
+        seed: args.seed,
+        region: args.region,
+        crop: args.crop,
+        output: args.output,
+        video: args.video.map(VideoConfig::new),
+    });
+    if let Some(camera) = &args.camera {
+        scene.select_camera(camera)?;
+    }
+
+    let render_type = match (args.frames, args.animation.as_deref()) {
+        (Some(frames), _) => RenderType::Frames(frames),
+        (None, Some(&[start_frame, frames])) => RenderType::Animation(start_frame, frames),
+        _ => RenderType::SingleFrame(args.frame),
+    };
+    // The first Ctrl-C drops the current pass and keeps the checkpoint of the finished ones, the
+    // second one exits right away
+    let cancelled = progress.cancel_flag();
+    ctrlc::set_handler(move || {
+        if cancelled.swap(true, Ordering::Relaxed) {
+            std::process::exit(130);
         }
+    })
+    .map_err(|error| format!("Failed to handle Ctrl-C: {}", error))?;
+
+    if args.all_cameras {
+        scene.render_all_cameras(render_type, &progress)?;
+    } else {
+        scene.render(render_type, &progress)?;
     }
+
+    if progress.cancel_flag().load(Ordering::Relaxed) {
+        return Err("The render was cancelled, continue it with --resume".to_string());
+    }
+    Ok(())
 }
 
-fn print_usage() {
-    println!("Usage:");
-    println!("  raytracer render <scene_file> frame");
-    println!("  raytracer render <scene_file> frame <frame>");
-    println!("  raytracer render <scene_file> animation <start_frame> <frames>");
+fn check(scene_file: &str) -> Result<(), String> {
+    let scene = Scene::from_file(scene_file)?;
+    println!(
+        "{} is valid: {} objects, {} cameras",
+        scene_file,
+        scene.world.objects.len(),
+        scene.camera_names().len()
+    );
+    Ok(())
+}
 
-    println!("  raytracer editor");
+fn init(scene_file: &str, force: bool) -> Result<(), String> {
+    if Path::new(scene_file).exists() && !force {
+        return Err(format!(
+            "{} already exists, pass --force to overwrite it",
+            scene_file
+        ));
+    }
+
+    Scene::create_example_scene(Scene::directory_of(scene_file)).save_config(scene_file)?;
+    println!("Created {}", scene_file);
+    Ok(())
 }
 
-fn main() {
-    if env::args().len() < 2 {
-        print_usage();
-        return;
-    }
-    match Command::from_args(env::args().collect()) {
-        Ok(command) => match command {
-            Command::Render(scene_file, render_type) => {
-                let mut scene = Scene::load_config(&scene_file);
-                scene.render(render_type);
-                scene.save_config(&scene_file);
-            }
-            Command::Editor(_) => {
-                print!("EDITOR IS WIP");
-            }
-        },
-        _ => print_usage(),
+fn info(scene_file: &str) -> Result<(), String> {
+    let scene = Scene::from_file(scene_file)?;
+    println!("Scene: {}", scene_file);
+    println!("Seed: {}", scene.seed());
+    println!("Output: {}", scene.output_template());
+    println!("Objects: {}", scene.world.objects.len());
+    println!("Cameras:");
+    for name in scene.camera_names() {
+        if let Some(config) = scene.camera_config(&name) {
+            println!("  {}: {}", name, describe_camera(config));
+        }
+    }
+    Ok(())
+}
+
+fn describe_camera(config: &CameraConfig) -> String {
+    let image_height = u32::max(1, (config.image_width as f64 / config.aspect_ratio) as u32);
+    let projection = match config.projection {
+        Projection::Perspective => "perspective",
+        Projection::Orthographic { .. } => "orthographic",
+        Projection::Fisheye { .. } => "fisheye",
+        Projection::Equirectangular => "equirectangular",
+        Projection::CubeMap => "cube map",
+        Projection::Realistic(_) => "realistic lens",
+    };
+
+    let mut description = format!(
+        "{}x{}, {} samples per pixel, max depth {}, {}",
+        config.image_width, image_height, config.samples_per_pixel, config.max_depth, projection
+    );
+    if config.stereo.is_some() {
+        description += ", stereo";
+    }
+    if config.spectral {
+        description += ", spectral";
+    }
+    description
+}
+
+fn convert(input: &str, output: &str) -> Result<(), String> {
+    if input.ends_with(CHECKPOINT_FILE_EXTENSION) {
+        let checkpoint = Checkpoint::load(input)
+            .map_err(|error| format!("Failed to read checkpoint {}: {}", input, error))?;
+        save_film(&checkpoint.film, output)?;
+    } else if input.ends_with(SCENE_FILE_EXTENSION) {
+        Scene::from_file(input)?.save_config(output)?;
+    } else {
+        return Err(format!(
+            "Don't know how to convert {}, expected a {} scene file or a {} file",
+            input, SCENE_FILE_EXTENSION, CHECKPOINT_FILE_EXTENSION
+        ));
+    }
+
+    println!("Converted {} to {}", input, output);
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    let result = match cli.command {
+        Command::Render(args) => render(*args),
+        Command::Check { scene_file } => check(&scene_file),
+        Command::Init { scene_file, force } => init(&scene_file, force),
+        Command::Info { scene_file } => info(&scene_file),
+        Command::Convert { input, output } => convert(&input, &output),
+        Command::Completions { shell } => {
+            let mut command = Cli::command();
+            let name = command.get_name().to_string();
+            clap_complete::generate(shell, &mut command, name, &mut io::stdout());
+            Ok(())
+        }
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render_args(args: &[&str]) -> Result<RenderArgs, clap::Error> {
+        let cli = Cli::try_parse_from(["cli", "render", "scene.rrtscene"].iter().chain(args))?;
+        let Command::Render(args) = cli.command else {
+            unreachable!("parsed a render command")
+        };
+        Ok(*args)
+    }
+
+    #[test]
+    fn arguments_are_consistent() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn regions_need_four_ordered_coordinates() {
+        let region = parse_region("1, 2,30,40").unwrap();
+        assert_eq!((region.x0, region.y0, region.x1, region.y1), (1, 2, 30, 40));
+        assert!(parse_region("1,2,30").is_err());
+        assert!(parse_region("1,2,30,40,50").is_err());
+        assert!(parse_region("1,2,x,40").is_err());
+        assert!(parse_region("-1,2,30,40").is_err());
+        assert!(parse_region("30,2,30,40").is_err());
+        assert!(parse_region("1,40,30,40").is_err());
+    }
+
+    #[test]
+    fn image_sizes_must_be_positive() {
+        let args = render_args(&["--width", "640", "--aspect-ratio", "1.5"]).unwrap();
+        assert_eq!(args.width, Some(640));
+        assert_eq!(args.aspect_ratio, Some(1.5));
+
+        assert!(render_args(&["--width", "0"]).is_err());
+        assert!(render_args(&["--samples", "0"]).is_err());
+        for aspect_ratio in ["0", "-1.5", "inf", "NaN", "wide"] {
+            let arg = format!("--aspect-ratio={}", aspect_ratio);
+            assert!(render_args(&[&arg]).is_err());
+        }
     }
 }
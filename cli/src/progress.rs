@@ -0,0 +1,126 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
+
+use engine::progress::{ProgressEvent, ProgressReporter};
+use indicatif::{ProgressBar, ProgressStyle};
+
+/// How the progress of a render is shown.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ProgressMode {
+    /// A progress bar per frame
+    Bar,
+    /// One JSON object per event on stdout
+    Json,
+    /// Nothing but warnings and errors
+    Quiet,
+}
+
+/// Shows the progress of a render on the terminal and cancels it once its cancel flag is set.
+pub struct CliProgress {
+    mode: ProgressMode,
+    cancelled: Arc<AtomicBool>,
+    // Bar of the frame being rendered
+    bar: Mutex<Option<ProgressBar>>,
+}
+
+impl CliProgress {
+    pub fn new(mode: ProgressMode) -> Self {
+        Self {
+            mode,
+            cancelled: Arc::new(AtomicBool::new(false)),
+            bar: Mutex::new(None),
+        }
+    }
+
+    /// Flag that cancels the render when set.
+    pub fn cancel_flag(&self) -> Arc<AtomicBool> {
+        self.cancelled.clone()
+    }
+
+    fn show_bar(&self, event: ProgressEvent) {
+        let mut bar = self.bar.lock().unwrap();
+        match event {
+            ProgressEvent::FrameStarted {
+                frame,
+                passes,
+                passes_done,
+                tiles,
+                ..
+            } => {
+                let new_bar = ProgressBar::new(passes as u64 * tiles as u64);
+                new_bar.set_style(
+                    ProgressStyle::with_template("{wide_bar} {pos}/{len} {msg}").unwrap(),
+                );
+                new_bar.set_position(passes_done as u64 * tiles as u64);
+                new_bar.set_message(format!("frame {}", frame));
+                *bar = Some(new_bar);
+            }
+            ProgressEvent::TileDone {
+                frame,
+                pass,
+                tiles_done,
+                tiles,
+                eta_in_seconds,
+                ..
+            } => {
+                if let Some(bar) = bar.as_ref() {
+                    // Tiles finish out of order, the bar only moves forward
+                    let position = pass as u64 * tiles as u64 + tiles_done as u64;
+                    if position > bar.position() {
+                        bar.set_position(position);
+                    }
+                    if let Some(eta) = eta_in_seconds {
+                        bar.set_message(format!("frame {}, {:.0}s left", frame, eta));
+                    }
+                }
+            }
+            ProgressEvent::FrameDone { cancelled, .. } => {
+                if let Some(bar) = bar.take() {
+                    if cancelled {
+                        bar.abandon();
+                    } else {
+                        bar.finish_with_message("done");
+                    }
+                }
+            }
+            ProgressEvent::CameraStarted { camera } => {
+                println!("Rendering camera {}.", camera)
+            }
+            ProgressEvent::Written { path } => match bar.as_ref() {
+                Some(bar) => bar.println(format!("Wrote {}.", path)),
+                None => println!("Wrote {}.", path),
+            },
+            ProgressEvent::Message { text } => match bar.as_ref() {
+                Some(bar) => bar.println(text),
+                None => println!("{}", text),
+            },
+            ProgressEvent::Warning { text } => match bar.as_ref() {
+                Some(bar) => bar.suspend(|| eprintln!("warning: {}", text)),
+                None => eprintln!("warning: {}", text),
+            },
+        }
+    }
+}
+
+impl ProgressReporter for CliProgress {
+    fn report(&self, event: ProgressEvent) {
+        match self.mode {
+            ProgressMode::Bar => self.show_bar(event),
+            ProgressMode::Json => match serde_json::to_string(&event) {
+                Ok(line) => println!("{}", line),
+                Err(error) => eprintln!("Failed to serialize the progress: {}", error),
+            },
+            ProgressMode::Quiet => {
+                if let ProgressEvent::Warning { text } = event {
+                    eprintln!("warning: {}", text);
+                }
+            }
+        }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
@@ -23,7 +23,7 @@ impl EditorMode {
         }
     }
 
-    fn button(&self, current: &EditorMode) -> Element<Message> {
+    fn button(&self, current: &EditorMode) -> Element<'_, Message> {
         let active_indicator = if self == current {
             text("🔵").width(20)
         } else {
@@ -66,7 +66,7 @@ async fn open_scene_file() -> Result<Arc<Scene>, Error> {
     match path {
         Some(p) => {
             let scene = Scene::load_config(p);
-            return Ok(Arc::new(scene));
+            Ok(Arc::new(scene))
         }
         None => Err(Error::IoError(io::ErrorKind::InvalidData)),
     }
@@ -136,7 +136,7 @@ impl EditorGUI {
         }
     }
 
-    pub fn view(&self) -> Element<Message> {
+    pub fn view(&self) -> Element<'_, Message> {
         let mode_tabbar: Element<Message> = row![
             EditorMode::Scene.button(&self.mode),
             EditorMode::Render.button(&self.mode),
@@ -152,8 +152,10 @@ impl EditorGUI {
 
                     for object in self.scene.world.objects.iter() {
                         let name = match object {
-                            HittableObject::Sphere(_) => format!("Sphere"),
-                            HittableObject::List(_) => format!("List"),
+                            HittableObject::Sphere(_) => "Sphere".to_string(),
+                            HittableObject::List(_) => "List".to_string(),
+                            HittableObject::ConstantMedium(_) => "Constant Medium".to_string(),
+                            HittableObject::Masked(_) => "Masked".to_string(),
                         };
                         children.push(text(name).into());
                     }
@@ -1,29 +1,53 @@
-use indicatif::ProgressBar;
 use math::{
     circle::degrees_to_radians,
     constants::INFINITY,
     interval::Interval,
     max::max_u32,
-    random::random_f64,
+    onb::Onb,
+    random::mix_seed,
     vec3::{Color, Point3, Vec3},
 };
 use rayon::prelude::*;
-use std::sync::Arc;
+use std::{
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
 
 extern crate image;
 
 use super::{
     animation::AnimationContext,
+    checkpoint::{fingerprint, Checkpoint, CheckpointSettings},
+    film::{Film, FilmSample},
+    filter::Filter,
     hittable::{
         hittable::{HitRecord, Hittable},
         hittable_list::HittableList,
     },
-    material::{lambertian::Lambertian, material::Material, object::MaterialObject},
+    lens::{LensConfig, LensFocus},
+    material::{
+        interior::{Interior, InteriorList},
+        material::{Material, ScatterKind},
+    },
+    medium::Medium,
+    progress::{NoProgress, ProgressEvent, ProgressReporter},
+    projection::Projection,
     ray::Ray,
+    sampler::sampler::{Sampler, SamplerType},
+    shutter::ShutterConfig,
+    spectrum::SampledWavelengths,
+    stereo::StereoConfig,
+    tile::{tiles, Tile, TileConfig},
 };
 use serde::{Deserialize, Serialize};
 
-#[derive(Deserialize, Serialize, Clone, Copy, Debug)]
+// Invisible boundaries a single path may cross, far more than any sensible scene nests
+const MAX_BOUNDARY_CROSSINGS: u32 = 1024;
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct CameraConfig {
     pub aspect_ratio: f64,
     pub image_width: u32,
@@ -40,6 +64,99 @@ pub struct CameraConfig {
     pub defocus_angle_in_degrees: f64,
     /// Distance from camera lookfrom point to plane of perfect focus
     pub focus_dist: f64,
+
+    #[serde(default)]
+    pub progressive: ProgressiveConfig,
+    #[serde(default)]
+    pub tiles: TileConfig,
+    #[serde(default)]
+    pub sampler: SamplerType,
+    /// Reconstruction filter the samples are splatted into the image with
+    #[serde(default)]
+    pub filter: Filter,
+    #[serde(default)]
+    pub path: PathConfig,
+    /// Atmospheric medium filling the space between objects. Rays that don't hit anything leave
+    /// it and reach the background unaffected.
+    #[serde(default)]
+    pub fog: Option<Medium>,
+    /// Trace every path at a few wavelengths instead of in RGB, required for dispersion
+    #[serde(default)]
+    pub spectral: bool,
+    /// How the image is mapped onto rays, `vfov_in_degrees` only applies to the perspective one
+    #[serde(default)]
+    pub projection: Projection,
+    /// Render a left and a right eye instead of a single image
+    #[serde(default)]
+    pub stereo: Option<StereoConfig>,
+    #[serde(default)]
+    pub lens: LensConfig,
+    #[serde(default)]
+    pub shutter: ShutterConfig,
+}
+
+/// Path termination settings. `max_depth` limits the total number of bounces, the per kind
+/// limits are applied on top of it.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug)]
+#[serde(default)]
+pub struct PathConfig {
+    /// Randomly terminate paths carrying little energy, off by default as it changes the noise
+    /// of existing scenes
+    pub russian_roulette: bool,
+    /// Bounces before russian roulette kicks in
+    pub russian_roulette_min_depth: u32,
+
+    pub max_diffuse_depth: Option<u32>,
+    pub max_specular_depth: Option<u32>,
+    pub max_transmission_depth: Option<u32>,
+    pub max_volume_depth: Option<u32>,
+}
+
+impl Default for PathConfig {
+    fn default() -> Self {
+        Self {
+            russian_roulette: false,
+            russian_roulette_min_depth: 3,
+            max_diffuse_depth: None,
+            max_specular_depth: None,
+            max_transmission_depth: None,
+            max_volume_depth: None,
+        }
+    }
+}
+
+/// Stop conditions and preview settings for progressive rendering. A frame is always rendered
+/// pass after pass with one sample per pixel and stops after `samples_per_pixel` passes at the
+/// latest, or earlier if one of the optional limits below is reached.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug)]
+#[serde(default)]
+pub struct ProgressiveConfig {
+    /// Wall clock time after which no further passes are started
+    pub time_budget_in_seconds: Option<f64>,
+    /// Average relative standard error of the pixels at which the frame is considered converged
+    pub noise_target: Option<f64>,
+    /// How often the intermediate result is handed out as a preview
+    pub preview_interval_in_seconds: Option<f64>,
+    /// How often the resumable render state is handed out to be written to disk, every minute
+    /// unless set
+    pub checkpoint_interval_in_seconds: Option<f64>,
+}
+
+impl Default for ProgressiveConfig {
+    fn default() -> Self {
+        Self {
+            time_budget_in_seconds: None,
+            noise_target: None,
+            preview_interval_in_seconds: None,
+            checkpoint_interval_in_seconds: Some(60.),
+        }
+    }
+}
+
+/// Intermediate results handed out while a frame is rendered progressively.
+pub enum RenderEvent<'a> {
+    Preview(&'a Film),
+    Checkpoint(&'a Checkpoint),
 }
 
 #[derive(Debug, Clone)]
@@ -47,11 +164,13 @@ pub struct Camera {
     pub config: CameraConfig,
 
     image_height: u32,
+    // Size of the rendered image, which holds both eyes in stereo
+    film_width: u32,
+    film_height: u32,
     center: Point3,
     pixel00_loc: Point3,
     pixel_delta_u: Vec3,
     pixel_delta_v: Vec3,
-    pixel_samples_scale: f64, // Color scale factor for a sum of pixel samples
 
     u: Vec3,
     v: Vec3,
@@ -59,6 +178,28 @@ pub struct Camera {
 
     defocus_disk_u: Vec3,
     defocus_disk_v: Vec3,
+
+    // Film placement of a realistic lens and the factor the samples are scaled with to make up
+    // for the light it blocks
+    lens_focus: Option<LensFocus>,
+    exposure: f64,
+
+    // Part of the image that is rendered, the rest of the film stays black
+    region: Option<Tile>,
+    // Separate images on the film, the eyes of a stereo image or the faces of a cube map, the
+    // filter doesn't spread samples across their borders
+    sub_images: Vec<Tile>,
+}
+
+impl CameraConfig {
+    /// Checks the settings that are only used once rendering starts, like whether the lens
+    /// system forms an image.
+    pub fn validate(&self) -> Result<(), String> {
+        if let Projection::Realistic(system) = &self.projection {
+            system.focus(self.focus_dist, self.aspect_ratio, &self.lens.aperture)?;
+        }
+        Ok(())
+    }
 }
 
 impl Camera {
@@ -66,62 +207,346 @@ impl Camera {
         self.image_height
     }
 
-    pub fn render_frame(&mut self, world: Arc<HittableList>, frame: u32) -> Vec<[u8; 3]> {
-        self.initialize();
+    /// Restricts rendering to `region` of the image, in pixels of the full image. The camera
+    /// keeps framing the full image. Fails if the region doesn't lie within the image.
+    pub fn set_region(&mut self, region: Option<Tile>) -> Result<(), String> {
+        self.initialize()?;
+        if let Some(region) = region {
+            if region.x1 > self.film_width || region.y1 > self.film_height {
+                return Err(format!(
+                    "The region {},{},{},{} doesn't lie within the image of {}x{} pixels",
+                    region.x0, region.y0, region.x1, region.y1, self.film_width, self.film_height
+                ));
+            }
+        }
+        self.region = region;
+        Ok(())
+    }
 
-        let bar =
-            ProgressBar::new((self.image_height as u64) * (self.config.image_width as u64) + 1);
+    // Settings a checkpoint of this camera is rendered with. The stop conditions and intervals
+    // of the progressive render don't change the samples, so a render can be continued with
+    // different ones.
+    fn checkpoint_settings(&self, world: &HittableList) -> Result<CheckpointSettings, String> {
+        #[derive(Serialize)]
+        struct Settings<'a> {
+            camera: CameraConfig,
+            world: &'a HittableList,
+        }
+
+        let settings = Settings {
+            camera: CameraConfig {
+                progressive: ProgressiveConfig::default(),
+                ..self.config.clone()
+            },
+            world,
+        };
+        Ok(CheckpointSettings {
+            region: self.region,
+            fingerprint: fingerprint(&settings)?,
+        })
+    }
+
+    pub fn render_frame(
+        &mut self,
+        world: Arc<HittableList>,
+        frame: u32,
+        seed: u64,
+    ) -> Result<Vec<[u8; 3]>, String> {
+        let checkpoint = self.start_frame(&world, frame, seed)?;
+        Ok(self
+            .render_frame_progressive(world, checkpoint, &NoProgress, |_| {})?
+            .film
+            .to_pixels())
+    }
 
-        let pixel_count = self.config.image_width as usize * self.image_height as usize;
+    /// Creates an empty checkpoint for rendering `frame` of `world` from scratch.
+    pub fn start_frame(
+        &mut self,
+        world: &HittableList,
+        frame: u32,
+        seed: u64,
+    ) -> Result<Checkpoint, String> {
+        self.initialize()?;
+        Ok(Checkpoint::new(
+            frame,
+            seed,
+            self.checkpoint_settings(world)?,
+            self.film_width,
+            self.film_height,
+        ))
+    }
 
-        // Create a parallel iterator over the pixels
-        let pixels = (0..pixel_count)
-            .into_par_iter() // Parallel iterator over pixel indices
-            .map(|idx| {
-                let i = idx % self.config.image_width as usize; // Calculate x position
-                let j = idx / self.config.image_width as usize; // Calculate y position
+    /// Whether a checkpoint can be continued with the current camera settings and `world`.
+    pub fn can_resume(
+        &mut self,
+        world: &HittableList,
+        checkpoint: &Checkpoint,
+    ) -> Result<bool, String> {
+        self.initialize()?;
+        Ok(checkpoint.settings == self.checkpoint_settings(world)?
+            && checkpoint.film.width() == self.film_width
+            && checkpoint.film.height() == self.film_height)
+    }
 
-                let mut pixel_color = Color::zero();
-                for _ in 0..self.config.samples_per_pixel {
-                    let ray = self.get_ray(i, j, frame);
-                    pixel_color = pixel_color + self.ray_color(&ray, self.config.max_depth, &world);
+    /// Continues rendering the frame of `checkpoint` pass after pass until one of the stop
+    /// conditions of the `ProgressiveConfig` is reached or `progress` cancels it. `on_event` is
+    /// called with previews and checkpoints at their configured intervals. Fails if the camera
+    /// can't form an image, like a lens system that can't focus.
+    pub fn render_frame_progressive<F: FnMut(RenderEvent)>(
+        &mut self,
+        world: Arc<HittableList>,
+        mut checkpoint: Checkpoint,
+        progress: &dyn ProgressReporter,
+        mut on_event: F,
+    ) -> Result<Checkpoint, String> {
+        self.initialize()?;
+
+        let tiles: Vec<Tile> = tiles(self.film_width, self.film_height, &self.config.tiles)
+            .into_iter()
+            .filter_map(|tile| match &self.region {
+                None => Some(tile),
+                Some(region) => {
+                    let clipped = Tile {
+                        x0: tile.x0.max(region.x0),
+                        y0: tile.y0.max(region.y0),
+                        x1: tile.x1.min(region.x1),
+                        y1: tile.y1.min(region.y1),
+                    };
+                    (clipped.x0 < clipped.x1 && clipped.y0 < clipped.y1).then_some(clipped)
                 }
-                let pixel = (self.pixel_samples_scale * pixel_color).to_pixel();
-                bar.inc(1);
-                return pixel;
             })
             .collect();
 
-        bar.finish();
+        let frame = checkpoint.frame;
+        let passes = self.config.samples_per_pixel;
+        let resumed_passes = checkpoint.film.passes();
+        progress.report(ProgressEvent::FrameStarted {
+            frame,
+            width: self.film_width,
+            height: self.film_height,
+            passes,
+            passes_done: resumed_passes,
+            tiles: tiles.len(),
+        });
+
+        let pixels_per_pass: u64 = tiles.iter().map(|tile| tile.pixel_count() as u64).sum();
+        let total_samples = pixels_per_pass * passes as u64;
 
-        return pixels;
+        let started = Instant::now();
+        let mut last_preview = started;
+        let mut last_checkpoint = started;
+        let mut cancelled = false;
+
+        while checkpoint.film.passes() < passes {
+            let pass = checkpoint.film.passes();
+            let tiles_done = AtomicUsize::new(0);
+            let pixels_done = AtomicU64::new(0);
+            let pass_samples = self.render_pass(&world, &checkpoint, &tiles, progress, |tile| {
+                let tiles_done = tiles_done.fetch_add(1, Ordering::Relaxed) + 1;
+                let pixels_done = pixels_done
+                    .fetch_add(tile.pixel_count() as u64, Ordering::Relaxed)
+                    + tile.pixel_count() as u64;
+
+                // The estimate only counts the samples rendered since the frame was resumed
+                let elapsed = started.elapsed().as_secs_f64();
+                let rendered = (pass - resumed_passes) as u64 * pixels_per_pass + pixels_done;
+                let samples_done = pass as u64 * pixels_per_pass + pixels_done;
+                let mut eta = (elapsed / rendered as f64) * (total_samples - samples_done) as f64;
+                if let Some(budget) = self.config.progressive.time_budget_in_seconds {
+                    eta = eta.min(f64::max(budget - elapsed, 0.));
+                }
+                progress.report(ProgressEvent::TileDone {
+                    frame,
+                    pass,
+                    tiles_done,
+                    tiles: tiles.len(),
+                    samples_done,
+                    samples: total_samples,
+                    elapsed_in_seconds: elapsed,
+                    eta_in_seconds: eta.is_finite().then_some(eta),
+                });
+            });
+
+            // A cancelled pass misses tiles, only whole passes go into the film
+            if progress.is_cancelled() {
+                cancelled = true;
+                break;
+            }
+            checkpoint
+                .film
+                .add_pass(&pass_samples, &self.config.filter, &self.sub_images);
+
+            if self.is_converged(&checkpoint.film, started) {
+                break;
+            }
+
+            let progressive = &self.config.progressive;
+            if let Some(interval) = progressive.preview_interval_in_seconds {
+                if last_preview.elapsed().as_secs_f64() >= interval {
+                    on_event(RenderEvent::Preview(&checkpoint.film));
+                    last_preview = Instant::now();
+                }
+            }
+            if let Some(interval) = progressive.checkpoint_interval_in_seconds {
+                if last_checkpoint.elapsed().as_secs_f64() >= interval {
+                    on_event(RenderEvent::Checkpoint(&checkpoint));
+                    last_checkpoint = Instant::now();
+                }
+            }
+        }
+
+        progress.report(ProgressEvent::FrameDone {
+            frame,
+            passes: checkpoint.film.passes(),
+            elapsed_in_seconds: started.elapsed().as_secs_f64(),
+            cancelled,
+        });
+
+        Ok(checkpoint)
+    }
+
+    // Traces a single sample for every pixel of the image. Tiles are handed to the render threads
+    // in their configured order, their samples are put back into tile order afterwards so they
+    // are always splatted into the film in the same order.
+    //
+    // Every pixel sample draws from its own sampler keyed by the seed, the frame, the pass and
+    // the pixel, so the result does not depend on which thread rendered the pixel, the tile
+    // layout or whether the render was resumed.
+    fn render_pass<F: Fn(&Tile) + Sync>(
+        &self,
+        world: &HittableList,
+        checkpoint: &Checkpoint,
+        tiles: &[Tile],
+        progress: &dyn ProgressReporter,
+        on_tile_done: F,
+    ) -> Vec<FilmSample> {
+        let image_width = self.film_width;
+        let frame_seed = mix_seed(checkpoint.seed, checkpoint.frame as u64);
+
+        let mut rendered_tiles: Vec<(usize, Vec<FilmSample>)> = tiles
+            .iter()
+            .enumerate()
+            .par_bridge()
+            .map(|(tile_index, tile)| {
+                if progress.is_cancelled() {
+                    return (tile_index, Vec::new());
+                }
+
+                let mut samples = Vec::with_capacity(tile.pixel_count());
+                for j in tile.y0..tile.y1 {
+                    for i in tile.x0..tile.x1 {
+                        let mut sampler = Sampler::new(
+                            self.config.sampler,
+                            (i, j),
+                            image_width,
+                            checkpoint.film.passes(),
+                            self.config.samples_per_pixel,
+                            frame_seed,
+                        );
+                        let offset = self.sample_square(&mut sampler);
+                        let mut wavelengths = self
+                            .config
+                            .spectral
+                            .then(|| SampledWavelengths::sample(sampler.get_1d()));
+                        let ray = self.get_ray(
+                            i as usize,
+                            j as usize,
+                            offset,
+                            checkpoint.frame,
+                            &mut wavelengths,
+                            &mut sampler,
+                        );
+                        let color = match ray {
+                            Some(ray) => {
+                                self.exposure
+                                    * self.ray_color(&ray, wavelengths, world, &mut sampler)
+                            }
+                            None => Color::zero(),
+                        };
+                        samples.push(FilmSample {
+                            pixel: (i, j),
+                            offset: (offset.x(), offset.y()),
+                            color,
+                        });
+                    }
+                }
+                on_tile_done(tile);
+                (tile_index, samples)
+            })
+            .collect();
+
+        rendered_tiles.sort_by_key(|(tile_index, _)| *tile_index);
+        rendered_tiles
+            .into_iter()
+            .flat_map(|(_, samples)| samples)
+            .collect()
+    }
+
+    fn is_converged(&self, film: &Film, started: Instant) -> bool {
+        let progressive = &self.config.progressive;
+
+        if let Some(budget) = progressive.time_budget_in_seconds {
+            if started.elapsed().as_secs_f64() >= budget {
+                return true;
+            }
+        }
+
+        if let Some(target) = progressive.noise_target {
+            if film.noise() <= target {
+                return true;
+            }
+        }
+
+        false
     }
 
     pub fn new_with_config(config: CameraConfig) -> Self {
         Camera {
             config,
             image_height: 10,
+            film_width: 10,
+            film_height: 10,
             center: Point3::zero(),
             pixel00_loc: Point3::zero(),
             pixel_delta_u: Vec3::zero(),
             pixel_delta_v: Vec3::zero(),
-            pixel_samples_scale: 1.0 / 10.,
             u: Vec3::zero(),
             v: Vec3::zero(),
             w: Vec3::zero(),
             defocus_disk_u: Vec3::zero(),
             defocus_disk_v: Vec3::zero(),
+            lens_focus: None,
+            exposure: 1.,
+            region: None,
+            sub_images: Vec::new(),
         }
     }
 
-    pub fn initialize(&mut self) {
+    pub fn initialize(&mut self) -> Result<(), String> {
         // Calculate the image height, and ensure that it's at least 1.
         self.image_height = max_u32(
             1,
             (self.config.image_width as f64 / self.config.aspect_ratio) as u32,
         );
-
-        self.pixel_samples_scale = 1.0 / (self.config.samples_per_pixel as f64);
+        (self.film_width, self.film_height) = match &self.config.stereo {
+            None => (self.config.image_width, self.image_height),
+            Some(stereo) => stereo.image_size(self.config.image_width, self.image_height),
+        };
+        let eyes = match &self.config.stereo {
+            None => vec![Tile {
+                x0: 0,
+                y0: 0,
+                x1: self.film_width,
+                y1: self.film_height,
+            }],
+            Some(stereo) => stereo
+                .eye_tiles(self.config.image_width, self.image_height)
+                .to_vec(),
+        };
+        self.sub_images = eyes
+            .into_iter()
+            .flat_map(|eye| self.config.projection.sub_images(eye))
+            .collect();
 
         self.center = self.config.lookfrom;
 
@@ -156,74 +581,499 @@ impl Camera {
             ));
         self.defocus_disk_u = self.u * defocus_radius;
         self.defocus_disk_v = self.v * defocus_radius;
+
+        self.lens_focus = match &self.config.projection {
+            Projection::Realistic(system) => Some(system.focus(
+                self.config.focus_dist,
+                self.config.image_width as f64 / self.image_height as f64,
+                &self.config.lens.aperture,
+            )?),
+            _ => None,
+        };
+        self.exposure = self.lens_focus.map_or(1., |focus| focus.exposure);
+        Ok(())
     }
 
-    // Construct a camera ray originating from the defocus disk and directed at a randomly
-    // sampled point around the pixel location i, j.
-    fn get_ray(&self, i: usize, j: usize, frame: u32) -> Ray {
-        let offset = self.sample_square();
-        let pixel_sample = self.pixel00_loc
-            + (((i as f64) + offset.x()) * self.pixel_delta_u)
-            + (((j as f64) + offset.y()) * self.pixel_delta_v);
+    // Construct a camera ray originating from the defocus disk and directed at the point offset
+    // from the pixel location i, j, or `None` if the lens blocks it.
+    fn get_ray(
+        &self,
+        i: usize,
+        j: usize,
+        offset: Vec3,
+        frame: u32,
+        wavelengths: &mut Option<SampledWavelengths>,
+        sampler: &mut Sampler,
+    ) -> Option<Ray> {
+        let lens = &self.config.lens;
 
-        let ray_origin = if self.config.defocus_angle_in_degrees <= 0. {
-            self.center
-        } else {
-            self.defocus_disc_sample()
+        // Always draw the lens sample so the following dimensions don't shift with the settings
+        let (lens_u, lens_v) = sampler.get_2d();
+        let lens_sample = match self.config.projection {
+            // Spread over the last element, the aperture stop inside the lens shapes it
+            Projection::Realistic(_) => {
+                let p = Vec3::in_unit_disk_from_sample(lens_u, lens_v);
+                (p.x(), p.y())
+            }
+            _ => lens.aperture.sample(lens_u, lens_v),
         };
-        let ray_direction = pixel_sample - ray_origin;
-        let time_at_frame = self.config.animation_meta.time_at_frame(frame);
-        let ray_time_interval = Interval {
-            min: time_at_frame,
-            max: time_at_frame + self.config.animation_meta.shutter_speed,
+
+        let (side, i, j) = match &self.config.stereo {
+            None => (0., i, j),
+            Some(stereo) => stereo.eye(i, j, self.config.image_width, self.image_height),
+        };
+        // Position of the sample in the image of a single eye, in pixels from its center
+        let width = self.config.image_width as f64;
+        let height = self.image_height as f64;
+        let mut x = (i as f64) + 0.5 + offset.x() - width / 2.;
+        let mut y = (j as f64) + 0.5 + offset.y() - height / 2.;
+        // Row a rolling shutter exposes the sample at, in [0, 1] from the top
+        let row = (j as f64 + 0.5 + offset.y()) / height;
+
+        let half_diagonal = f64::hypot(width, height) / 2.;
+        if lens.is_vignetted(lens_sample, x / half_diagonal, -y / half_diagonal) {
+            return None;
+        }
+
+        let mut focus_scale = 1.;
+        if let Some(wavelengths) = wavelengths.as_mut().filter(|_| lens.is_chromatic()) {
+            // The image of the wavelength is larger, the pixel sees a point closer to the center
+            let (image_scale, focus) = lens.chromatic_aberration(wavelengths.hero());
+            x /= image_scale;
+            y /= image_scale;
+            focus_scale = focus;
+            wavelengths.terminate_secondary();
+        }
+
+        let (ray_origin, ray_direction) = match &self.config.projection {
+            Projection::Realistic(system) => {
+                let (origin, direction) = system.ray(
+                    self.lens_focus.as_ref()?,
+                    x / width + 0.5,
+                    y / height + 0.5,
+                    lens_sample,
+                    wavelengths.map(|wavelengths| wavelengths.hero()),
+                    &lens.aperture,
+                )?;
+                if system.is_dispersive() {
+                    if let Some(wavelengths) = wavelengths {
+                        wavelengths.terminate_secondary();
+                    }
+                }
+
+                // The eyes of a stereo rig look parallel through their own lens
+                let eye = side
+                    * self
+                        .config
+                        .stereo
+                        .map_or(0., |stereo| stereo.interocular_distance)
+                    / 2.;
+                (
+                    self.center + self.to_world(origin) + eye * self.u,
+                    self.to_world(direction),
+                )
+            }
+            _ => {
+                let (origin, direction) = self.pinhole_ray(x, y);
+                let (origin, direction) = match &self.config.stereo {
+                    None => (origin, direction),
+                    Some(stereo) => self.eye_ray(stereo, side, origin, direction),
+                };
+                self.defocus(origin, direction, lens_sample, focus_scale)
+            }
         };
-        let ray_time = ray_time_interval.random();
 
-        Ray::new_with_time(ray_origin, ray_direction, ray_time)
+        let ray_time = self.config.shutter.sample_time(
+            self.config.animation_meta.time_at_frame(frame),
+            self.config.animation_meta.shutter_speed,
+            row,
+            sampler.get_1d(),
+        );
+
+        Some(Ray::new_with_time(ray_origin, ray_direction, ray_time))
     }
 
-    // Returns the vector to a random point in the [-.5,-.5]-[+.5,+.5] unit square.
-    fn sample_square(&self) -> Vec3 {
-        Vec3::new(random_f64() - 0.5, random_f64() - 0.5, 0.)
+    // Ray through the image position `x`, `y` in pixels from the center of a single eye,
+    // starting at the center of the lens. The direction reaches the point in focus after
+    // `focus_dist` times its length, which puts the focus on a plane for the perspective
+    // projection.
+    fn pinhole_ray(&self, x: f64, y: f64) -> (Point3, Vec3) {
+        let width = self.config.image_width as f64;
+        let height = self.image_height as f64;
+        match &self.config.projection {
+            Projection::Perspective => {
+                let pixel_sample = self.pixel00_loc
+                    + (x + width / 2. - 0.5) * self.pixel_delta_u
+                    + (y + height / 2. - 0.5) * self.pixel_delta_v;
+                (
+                    self.center,
+                    (pixel_sample - self.center) / self.config.focus_dist,
+                )
+            }
+            projection => {
+                let (origin, direction) = projection.camera_ray(
+                    x / width + 0.5,
+                    y / height + 0.5,
+                    self.config.vfov_in_degrees,
+                    width / height,
+                );
+                (
+                    self.center + self.to_world(origin),
+                    self.to_world(direction),
+                )
+            }
+        }
+    }
+
+    // Moves a pinhole ray to the eye on `side` and aims it at the point it passes at the
+    // convergence distance.
+    fn eye_ray(
+        &self,
+        stereo: &StereoConfig,
+        side: f64,
+        origin: Point3,
+        direction: Vec3,
+    ) -> (Point3, Vec3) {
+        let half_distance = side * stereo.interocular_distance / 2.;
+        let (eye, convergence_scale) = match self.config.projection {
+            Projection::Perspective | Projection::Orthographic { .. } => {
+                // Measured along the view axis, the convergence distance is a plane
+                (origin + half_distance * self.u, -direction.dot(&self.w))
+            }
+            _ => {
+                // To the right of the horizontal part of the direction, which shrinks
+                // towards the poles
+                (
+                    origin + half_distance * direction.cross(&self.v),
+                    direction.length(),
+                )
+            }
+        };
+
+        match stereo.convergence_distance {
+            None => (eye, direction),
+            Some(distance) => {
+                let target = origin + distance / convergence_scale * direction;
+                // Keep the length along the view, so the focus stays where it was
+                (eye, (target - eye) * (convergence_scale / distance))
+            }
+        }
+    }
+
+    // Moves the origin of a pinhole ray onto the lens and aims it at the point in focus. The
+    // perspective lens faces along the view, the others lie across the ray, so panoramas focus
+    // on a sphere around the camera rather than a plane.
+    fn defocus(
+        &self,
+        origin: Point3,
+        direction: Vec3,
+        lens_sample: (f64, f64),
+        focus_scale: f64,
+    ) -> (Point3, Vec3) {
+        if self.config.defocus_angle_in_degrees <= 0. {
+            return (origin, direction);
+        }
+
+        let focus_point = origin + focus_scale * self.config.focus_dist * direction;
+        let lens_origin = match self.config.projection {
+            Projection::Perspective => {
+                origin + lens_sample.0 * self.defocus_disk_u + lens_sample.1 * self.defocus_disk_v
+            }
+            _ => {
+                let lens = Onb::from_normal(direction.unit_vector());
+                let defocus_radius = self.defocus_disk_u.length();
+                origin + defocus_radius * (lens_sample.0 * lens.u + lens_sample.1 * lens.v)
+            }
+        };
+        (lens_origin, focus_point - lens_origin)
     }
 
-    // Returns a random point in the camera defocus disk.
-    fn defocus_disc_sample(&self) -> Vec3 {
-        // TODO: This can be a vec2
-        let p = Vec3::random_in_unit_disk();
-        return self.center + (p.x() * self.defocus_disk_u) + (p.y() * self.defocus_disk_v);
+    // Turns a direction in camera space, x to the right, y up and z backwards, into world space.
+    fn to_world(&self, v: Vec3) -> Vec3 {
+        v.x() * self.u + v.y() * self.v + v.z() * self.w
     }
 
-    fn ray_color(&self, r: &Ray, depth: u32, world: &HittableList) -> Color {
+    // Returns the vector to a random point in the [-.5,-.5]-[+.5,+.5] unit square.
+    fn sample_square(&self, sampler: &mut Sampler) -> Vec3 {
+        let (u, v) = sampler.get_2d();
+        Vec3::new(u - 0.5, v - 0.5, 0.)
+    }
+
+    // Traces a path through the world, tracking how much of the light arriving along it makes
+    // it back to the camera (the throughput). Between surfaces the ray travels through the
+    // innermost medium it entered, or the fog outside of all of them, and may scatter inside it.
+    //
+    // In spectral mode the components of the colors along the path hold the values at the
+    // sampled wavelengths instead of red, green and blue, and are only converted back at the end.
+    fn ray_color(
+        &self,
+        r: &Ray,
+        mut wavelengths: Option<SampledWavelengths>,
+        world: &HittableList,
+        sampler: &mut Sampler,
+    ) -> Color {
+        let path = &self.config.path;
+
+        let mut rec = HitRecord::default();
+
+        let mut ray = r.with_wavelength(wavelengths.map(|wavelengths| wavelengths.hero()));
+        let mut throughput = Color::one();
+        let mut radiance = Color::zero();
+        let mut media: Vec<Medium> = Vec::new();
+        let mut interior = InteriorList::default();
+
+        let mut diffuse_bounces = 0;
+        let mut specular_bounces = 0;
+        let mut transmission_bounces = 0;
+        let mut volume_bounces = 0;
+
+        // Crossing an invisible boundary is no bounce, so `depth` only counts the scattering
+        // events. The crossings have a limit of their own, which only stops paths that are stuck.
+        let mut depth = 0;
+        let mut crossings = 0;
+
         // If we've exceeded the ray bounce limit, no more light is gathered.
-        if depth <= 0 {
-            return Color::zero();
-        }
-
-        let mut rec: HitRecord = HitRecord {
-            t: 0.,
-            p: Point3::zero(),
-            normal: Vec3::zero(),
-            front_face: false,
-            material: MaterialObject::Lambertian(Lambertian {
-                albedo: Color::zero(),
-            }),
-        };
+        while depth < self.config.max_depth {
+            if !world.hit(&ray, Interval::new(0.001, INFINITY), &mut rec) {
+                radiance =
+                    radiance + throughput * Self::path_color(Self::background(&ray), &wavelengths);
+                break;
+            }
 
-        if world.hit(r, Interval::new(0.001, INFINITY), &mut rec) {
-            let mut ray_scattered = Ray::new(rec.p, rec.normal + Vec3::random_unit_vector());
-            let mut attenuation = Color::zero();
-            if rec
-                .material
-                .scatter(r, &rec, &mut attenuation, &mut ray_scattered)
+            // Free flight sampling, the distance is sampled proportional to the transmittance so
+            // only the albedo is left to weight a scattering event with
+            let interaction = media
+                .last()
+                .copied()
+                .or(self.config.fog)
+                .map(|medium| {
+                    let t = medium.sample_distance(sampler.get_1d()) / ray.direction().length();
+                    (medium, t)
+                })
+                .filter(|(_, t)| *t < rec.t);
+
+            // Beer-Lambert absorption along the distance travelled inside the current dielectric
+            let travelled = interaction.map_or(rec.t, |(_, t)| t) * ray.direction().length();
+            throughput =
+                throughput * Self::path_color(interior.transmittance(travelled), &wavelengths);
+
+            // The side the ray comes from, or the one behind the surface when it leaves an object
+            rec.outside_refraction_index = if rec.front_face {
+                interior.refraction_index()
+            } else {
+                interior.refraction_index_outside_of(rec.object_id)
+            };
+            let dielectric = rec.material.interior(&rec, ray.wavelength());
+
+            let (kind, attenuation, ray_scattered) = if let Some((medium, t)) = interaction {
+                let (u, v) = sampler.get_2d();
+                let direction = medium.phase.sample(ray.direction().unit_vector(), u, v);
+                (
+                    ScatterKind::Volume,
+                    medium.albedo,
+                    Ray::new_with_time(ray.at(t), direction, ray.time()),
+                )
+            } else if rec.medium.is_some_and(|boundary| !boundary.surface) {
+                // The boundary itself is invisible, the ray just moves into or out of the medium
+                Self::cross_medium_boundary(&mut media, &rec);
+                ray = Ray::new_with_time(rec.p, ray.direction(), ray.time())
+                    .with_wavelength(ray.wavelength());
+                crossings += 1;
+                if crossings >= MAX_BOUNDARY_CROSSINGS {
+                    break;
+                }
+                continue;
+            } else if let Some(dielectric) =
+                dielectric.filter(|dielectric| interior.is_false_intersection(dielectric))
             {
-                return attenuation * self.ray_color(&ray_scattered, depth - 1, world);
+                // Hidden inside a dielectric of higher priority, only keep track of being inside
+                Self::cross_dielectric_boundary(&mut interior, dielectric, &rec);
+                ray = Ray::new_with_time(rec.p, ray.direction(), ray.time())
+                    .with_wavelength(ray.wavelength());
+                crossings += 1;
+                if crossings >= MAX_BOUNDARY_CROSSINGS {
+                    break;
+                }
+                continue;
+            } else {
+                let mut ray_scattered = Ray::new(rec.p, rec.normal);
+                let mut attenuation = Color::zero();
+                let Some(kind) =
+                    rec.material
+                        .scatter(&ray, &rec, &mut attenuation, &mut ray_scattered, sampler)
+                else {
+                    break;
+                };
+                if ray_scattered.direction().dot(&rec.normal) < 0. {
+                    Self::cross_medium_boundary(&mut media, &rec);
+                    if let Some(dielectric) = dielectric {
+                        Self::cross_dielectric_boundary(&mut interior, dielectric, &rec);
+                    }
+                }
+                if rec.material.is_dispersive() {
+                    if let Some(wavelengths) = &mut wavelengths {
+                        wavelengths.terminate_secondary();
+                    }
+                }
+                (kind, attenuation, ray_scattered)
+            };
+
+            let (bounces, max_bounces) = match kind {
+                ScatterKind::Diffuse => (&mut diffuse_bounces, path.max_diffuse_depth),
+                ScatterKind::Specular => (&mut specular_bounces, path.max_specular_depth),
+                ScatterKind::Transmission => {
+                    (&mut transmission_bounces, path.max_transmission_depth)
+                }
+                ScatterKind::Volume => (&mut volume_bounces, path.max_volume_depth),
+            };
+            *bounces += 1;
+            if max_bounces.is_some_and(|max_bounces| *bounces > max_bounces) {
+                break;
             }
-            return Color::zero();
+
+            throughput = throughput * Self::path_color(attenuation, &wavelengths);
+            ray = ray_scattered.with_wavelength(ray.wavelength());
+
+            // Terminate paths that carry little energy randomly and boost the survivors, which
+            // keeps the estimate unbiased
+            if path.russian_roulette && depth + 1 >= path.russian_roulette_min_depth {
+                let survival_probability = f64::min(throughput.max_component(), 0.95);
+                if sampler.get_1d() >= survival_probability {
+                    break;
+                }
+                throughput = throughput / survival_probability;
+            }
+            depth += 1;
+        }
+
+        match wavelengths {
+            Some(wavelengths) => wavelengths.to_rgb(radiance),
+            None => radiance,
+        }
+    }
+
+    fn cross_dielectric_boundary(
+        interior: &mut InteriorList,
+        dielectric: Interior,
+        rec: &HitRecord,
+    ) {
+        if rec.front_face {
+            interior.enter(dielectric);
+        } else {
+            interior.leave(&dielectric);
         }
+    }
 
+    // Converts a color into the space the path is traced in.
+    fn path_color(color: Color, wavelengths: &Option<SampledWavelengths>) -> Color {
+        match wavelengths {
+            Some(wavelengths) => wavelengths.upsample(color),
+            None => color,
+        }
+    }
+
+    // Enters the medium behind the surface of `rec` when the ray crosses it from the outside and
+    // leaves it again when crossing from the inside.
+    fn cross_medium_boundary(media: &mut Vec<Medium>, rec: &HitRecord) {
+        let Some(boundary) = rec.medium else {
+            return;
+        };
+
+        if rec.front_face {
+            media.push(boundary.medium);
+        } else if let Some(index) = media.iter().rposition(|medium| *medium == boundary.medium) {
+            media.remove(index);
+        }
+    }
+
+    fn background(r: &Ray) -> Color {
         let unit_direction = r.direction().unit_vector();
         let a = 0.5 * (unit_direction.y() + 1.0);
-        return (1.0 - a) * Color::new(1.0, 1.0, 1.0) + a * Color::new(0.5, 0.7, 1.0);
+        (1.0 - a) * Color::new(1.0, 1.0, 1.0) + a * Color::new(0.5, 0.7, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        animation::AnimatedVec3,
+        hittable::{
+            constant_medium::ConstantMedium, object::HittableObject, primitives::sphere::Sphere,
+        },
+        lens::LensSystem,
+        material::{lambertian::Lambertian, object::MaterialObject},
+        scene::scene::{Scene, DEFAULT_CAMERA},
+    };
+
+    fn camera(max_depth: u32) -> Camera {
+        let scene = Scene::create_example_scene(String::new());
+        let mut config = scene.camera_config(DEFAULT_CAMERA).unwrap().clone();
+        config.image_width = 8;
+        config.samples_per_pixel = 1;
+        config.max_depth = max_depth;
+        config.lookfrom = Point3::new(0., 0., 5.);
+        config.lookat = Point3::zero();
+        config.defocus_angle_in_degrees = 0.;
+        Camera::new_with_config(config)
+    }
+
+    // Empty fog around the origin, which the camera looks through
+    fn fog(radius: f64) -> HittableObject {
+        HittableObject::ConstantMedium(ConstantMedium {
+            boundary: Box::new(HittableObject::Sphere(Box::new(Sphere::new(
+                AnimatedVec3::static_value(Point3::zero()),
+                radius,
+                MaterialObject::Lambertian(Lambertian {
+                    albedo: Color::new(0.5, 0.5, 0.5),
+                }),
+            )))),
+            medium: Medium {
+                density: 0.,
+                albedo: Color::one(),
+                phase: Default::default(),
+            },
+            surface: false,
+        })
+    }
+
+    #[test]
+    fn invisible_boundaries_are_no_bounces() {
+        let empty = Arc::new(HittableList::new());
+        let mut nested = HittableList::new();
+        for radius in [1., 1.5, 2.] {
+            nested.add(fog(radius));
+        }
+        let nested = Arc::new(nested);
+
+        let background = camera(1).render_frame(empty, 0, 42).unwrap();
+        let through_fog = camera(1).render_frame(nested, 0, 42).unwrap();
+        assert_eq!(through_fog, background);
+    }
+
+    #[test]
+    fn lenses_that_cant_focus_fail_the_render() {
+        let system: LensSystem = toml::from_str(
+            r#"
+            elements = [
+                { curvature_radius = 50, thickness = 5, refraction_index = 1.5, aperture_diameter = 20 },
+                { curvature_radius = -50, thickness = 1, aperture_diameter = 20 },
+            ]
+            "#,
+        )
+        .unwrap();
+        let mut camera = camera(1);
+        camera.config.projection = Projection::Realistic(system);
+
+        camera.config.focus_dist = 5.;
+        assert!(camera
+            .render_frame(Arc::new(HittableList::new()), 0, 42)
+            .is_ok());
+        // Closer than four times the focal length of 50 mm
+        camera.config.focus_dist = 0.1;
+        assert!(camera.config.validate().is_err());
+        assert!(camera
+            .render_frame(Arc::new(HittableList::new()), 0, 42)
+            .is_err());
     }
 }
@@ -0,0 +1,264 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Write},
+};
+
+use serde::Serialize;
+
+use crate::{
+    film::{read_u32, read_u64, Film},
+    tile::Tile,
+};
+
+const MAGIC: &[u8; 8] = b"RRTCHKPT";
+const VERSION: u32 = 4;
+
+/// Everything needed to continue a progressive render: the accumulated film and the seed all
+/// per pixel random streams are derived from. Resuming from a checkpoint yields the same image
+/// as an uninterrupted render.
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    pub frame: u32,
+    pub seed: u64,
+    pub settings: CheckpointSettings,
+    pub film: Film,
+}
+
+/// Settings the samples of a checkpoint depend on, it can only be continued with the same ones.
+/// Everything but the region is compared by the `fingerprint` of the camera and the world.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CheckpointSettings {
+    pub region: Option<Tile>,
+    pub fingerprint: u64,
+}
+
+impl Checkpoint {
+    pub fn new(
+        frame: u32,
+        seed: u64,
+        settings: CheckpointSettings,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        Self {
+            frame,
+            seed,
+            settings,
+            film: Film::new(width, height),
+        }
+    }
+
+    /// Writes the checkpoint to a temporary file first and moves it in place afterwards, so
+    /// killing the render while saving never leaves a truncated checkpoint behind.
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let temp_path = format!("{}.tmp", path);
+
+        {
+            let mut writer = BufWriter::new(File::create(&temp_path)?);
+            writer.write_all(MAGIC)?;
+            writer.write_all(&VERSION.to_le_bytes())?;
+            writer.write_all(&self.frame.to_le_bytes())?;
+            writer.write_all(&self.seed.to_le_bytes())?;
+            self.settings.write_to(&mut writer)?;
+            self.film.write_to(&mut writer)?;
+            writer.flush()?;
+        }
+
+        fs::rename(temp_path, path)
+    }
+
+    pub fn load(path: &str) -> io::Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a render checkpoint",
+            ));
+        }
+
+        let version = read_u32(&mut reader)?;
+        if version != VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported checkpoint version {}", version),
+            ));
+        }
+
+        let frame = read_u32(&mut reader)?;
+        let seed = read_u64(&mut reader)?;
+        let settings = CheckpointSettings::read_from(&mut reader)?;
+        let film = Film::read_from(&mut reader)?;
+
+        Ok(Self {
+            frame,
+            seed,
+            settings,
+            film,
+        })
+    }
+}
+
+impl CheckpointSettings {
+    // A region is stored as a flag followed by its corners, which are 0 without a region
+    fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let region = self.region.unwrap_or(Tile {
+            x0: 0,
+            y0: 0,
+            x1: 0,
+            y1: 0,
+        });
+        let values = [
+            self.region.is_some() as u32,
+            region.x0,
+            region.y0,
+            region.x1,
+            region.y1,
+        ];
+        for value in values {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        writer.write_all(&self.fingerprint.to_le_bytes())
+    }
+
+    fn read_from<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let has_region = read_u32(reader)? != 0;
+        let region = Tile {
+            x0: read_u32(reader)?,
+            y0: read_u32(reader)?,
+            x1: read_u32(reader)?,
+            y1: read_u32(reader)?,
+        };
+        Ok(Self {
+            region: has_region.then_some(region),
+            fingerprint: read_u64(reader)?,
+        })
+    }
+}
+
+/// Hash of the scene file representation of `value`. Unlike `Hash` it stays the same across
+/// builds, so checkpoints of an older binary are still recognized.
+pub fn fingerprint<T: Serialize>(value: &T) -> Result<u64, String> {
+    let contents = toml::to_string(value)
+        .map_err(|error| format!("Failed to serialize the render settings: {}", error))?;
+
+    // 64 bit FNV-1a
+    Ok(contents.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    };
+
+    use super::*;
+    use crate::{
+        camera::Camera,
+        filter::Filter,
+        progress::{NoProgress, ProgressEvent, ProgressReporter},
+        scene::scene::{Scene, DEFAULT_CAMERA},
+    };
+
+    // Cancels the render as soon as the pass `pass` starts, so the passes before it are kept
+    struct CancelAtPass {
+        pass: u32,
+        cancelled: AtomicBool,
+    }
+
+    impl ProgressReporter for CancelAtPass {
+        fn report(&self, event: ProgressEvent) {
+            if let ProgressEvent::TileDone { pass, .. } = event {
+                if pass >= self.pass {
+                    self.cancelled.store(true, Ordering::Relaxed);
+                }
+            }
+        }
+
+        fn is_cancelled(&self) -> bool {
+            self.cancelled.load(Ordering::Relaxed)
+        }
+    }
+
+    fn bytes(film: &Film) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        film.write_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn resumed_render_matches_an_uninterrupted_one() {
+        let scene = Scene::create_example_scene(String::new());
+        let world = Arc::new(scene.world.clone());
+        let mut config = scene.camera_config(DEFAULT_CAMERA).unwrap().clone();
+        config.image_width = 24;
+        config.samples_per_pixel = 4;
+        config.tiles.size = 8;
+
+        let mut camera = Camera::new_with_config(config.clone());
+        let checkpoint = camera.start_frame(&world, 3, 42).unwrap();
+        let uninterrupted = camera
+            .render_frame_progressive(world.clone(), checkpoint, &NoProgress, |_| {})
+            .unwrap();
+        assert_eq!(uninterrupted.film.passes(), 4);
+
+        let mut camera = Camera::new_with_config(config.clone());
+        let checkpoint = camera.start_frame(&world, 3, 42).unwrap();
+        let cancel = CancelAtPass {
+            pass: 2,
+            cancelled: AtomicBool::new(false),
+        };
+        let interrupted = camera
+            .render_frame_progressive(world.clone(), checkpoint, &cancel, |_| {})
+            .unwrap();
+        assert_eq!(interrupted.film.passes(), 2);
+
+        let path = std::env::temp_dir().join(format!("resume-{}.checkpoint", std::process::id()));
+        let path = path.to_string_lossy();
+        interrupted.save(&path).unwrap();
+        let loaded = Checkpoint::load(&path).unwrap();
+        fs::remove_file(path.as_ref()).unwrap();
+
+        let mut camera = Camera::new_with_config(config);
+        assert!(camera.can_resume(&world, &loaded).unwrap());
+        let resumed = camera
+            .render_frame_progressive(world, loaded, &NoProgress, |_| {})
+            .unwrap();
+        assert_eq!(resumed.film.passes(), 4);
+        assert_eq!(bytes(&resumed.film), bytes(&uninterrupted.film));
+    }
+
+    #[test]
+    fn checkpoints_of_other_settings_are_not_resumed() {
+        let scene = Scene::create_example_scene(String::new());
+        let config = scene.camera_config(DEFAULT_CAMERA).unwrap().clone();
+        let mut camera = Camera::new_with_config(config.clone());
+        let checkpoint = camera.start_frame(&scene.world, 0, 42).unwrap();
+
+        // Stop conditions only decide how many passes are rendered
+        let mut budget = config.clone();
+        budget.progressive.time_budget_in_seconds = Some(10.);
+        let mut camera = Camera::new_with_config(budget);
+        assert!(camera.can_resume(&scene.world, &checkpoint).unwrap());
+
+        let mut filter = config.clone();
+        filter.filter = Filter::Tent { radius: 1. };
+        let mut camera = Camera::new_with_config(filter);
+        assert!(!camera.can_resume(&scene.world, &checkpoint).unwrap());
+
+        let mut path = config.clone();
+        path.path.russian_roulette = true;
+        let mut camera = Camera::new_with_config(path);
+        assert!(!camera.can_resume(&scene.world, &checkpoint).unwrap());
+
+        let mut world = scene.world.clone();
+        world.objects.pop();
+        let mut camera = Camera::new_with_config(config);
+        assert!(!camera.can_resume(&world, &checkpoint).unwrap());
+    }
+}
@@ -0,0 +1,353 @@
+use std::io::{self, Read, Write};
+
+use math::vec3::Color;
+
+use crate::{filter::Filter, tile::Tile};
+
+/// Camera sample on its way into the film, `offset` is relative to the pixel center.
+#[derive(Debug, Clone, Copy)]
+pub struct FilmSample {
+    pub pixel: (u32, u32),
+    pub offset: (f64, f64),
+    pub color: Color,
+}
+
+/// Accumulation buffer for progressive rendering. Every pass adds one sample per pixel which is
+/// splatted into the surrounding pixels by the reconstruction filter, the pixel estimate is the
+/// weighted mean of everything splatted into it.
+#[derive(Debug, Clone)]
+pub struct Film {
+    width: u32,
+    height: u32,
+    passes: u32,
+
+    sum: Vec<Color>,
+    sum_weights: Vec<f64>,
+
+    // Statistics used to estimate the remaining noise. They use absolute weights, so filters
+    // with negative lobes don't cancel out the variance.
+    sum_squared_luminance: Vec<f64>,
+    sum_absolute_weights: Vec<f64>,
+    sum_squared_weights: Vec<f64>,
+}
+
+impl Film {
+    pub fn new(width: u32, height: u32) -> Self {
+        let pixel_count = width as usize * height as usize;
+        Self {
+            width,
+            height,
+            passes: 0,
+            sum: vec![Color::zero(); pixel_count],
+            sum_weights: vec![0.; pixel_count],
+            sum_squared_luminance: vec![0.; pixel_count],
+            sum_absolute_weights: vec![0.; pixel_count],
+            sum_squared_weights: vec![0.; pixel_count],
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Number of samples taken in every pixel.
+    pub fn passes(&self) -> u32 {
+        self.passes
+    }
+
+    /// Splats one pass worth of samples into the film. A sample only reaches the pixels of the
+    /// one of `sub_images` its pixel lies in, or of the whole film if it lies in none.
+    pub fn add_pass(&mut self, samples: &[FilmSample], filter: &Filter, sub_images: &[Tile]) {
+        let radius = filter.radius();
+        let film = Tile {
+            x0: 0,
+            y0: 0,
+            x1: self.width,
+            y1: self.height,
+        };
+
+        for sample in samples {
+            let bounds = sub_images
+                .iter()
+                .find(|image| image.contains(sample.pixel))
+                .unwrap_or(&film);
+
+            // Continuous image position of the sample, pixel centers sit at half coordinates
+            let x = sample.pixel.0 as f64 + 0.5 + sample.offset.0;
+            let y = sample.pixel.1 as f64 + 0.5 + sample.offset.1;
+
+            let x0 = f64::max((x - 0.5 - radius).ceil(), bounds.x0 as f64) as u32;
+            let x1 = f64::min((x - 0.5 + radius).floor(), bounds.x1 as f64 - 1.) as i64;
+            let y0 = f64::max((y - 0.5 - radius).ceil(), bounds.y0 as f64) as u32;
+            let y1 = f64::min((y - 0.5 + radius).floor(), bounds.y1 as f64 - 1.) as i64;
+
+            let luminance = sample.color.luminance();
+            for py in (y0 as i64)..=y1 {
+                for px in (x0 as i64)..=x1 {
+                    let weight = filter.evaluate(px as f64 + 0.5 - x, py as f64 + 0.5 - y);
+                    if weight == 0. {
+                        continue;
+                    }
+
+                    let idx = py as usize * self.width as usize + px as usize;
+                    self.sum[idx] = self.sum[idx] + weight * sample.color;
+                    self.sum_weights[idx] += weight;
+                    self.sum_squared_luminance[idx] += weight.abs() * luminance * luminance;
+                    self.sum_absolute_weights[idx] += weight.abs();
+                    self.sum_squared_weights[idx] += weight * weight;
+                }
+            }
+        }
+        self.passes += 1;
+    }
+
+    pub fn mean(&self, idx: usize) -> Color {
+        if self.sum_weights[idx] <= 0. {
+            return Color::zero();
+        }
+        self.sum[idx] / self.sum_weights[idx]
+    }
+
+    pub fn to_pixels(&self) -> Vec<[u8; 3]> {
+        (0..self.sum.len())
+            .map(|idx| self.mean(idx).to_pixel())
+            .collect()
+    }
+
+    /// Average relative standard error of the pixel luminance estimates. Returns infinity until
+    /// at least two passes have been accumulated since no variance can be estimated before that.
+    pub fn noise(&self) -> f64 {
+        if self.passes < 2 || self.sum.is_empty() {
+            return f64::INFINITY;
+        }
+
+        // Pixels outside of a render region never receive samples and are left out
+        let rendered = (0..self.sum.len())
+            .filter(|&idx| self.sum_absolute_weights[idx] > 0.)
+            .count();
+        if rendered == 0 {
+            return f64::INFINITY;
+        }
+
+        let total: f64 = (0..self.sum.len())
+            .map(|idx| {
+                let absolute_weights = self.sum_absolute_weights[idx];
+                if absolute_weights <= 0. {
+                    return 0.;
+                }
+
+                let mean = self.mean(idx).luminance();
+                let variance = f64::max(
+                    self.sum_squared_luminance[idx] / absolute_weights - mean * mean,
+                    0.,
+                );
+                // Effective number of samples of the weighted mean
+                let n = absolute_weights * absolute_weights / self.sum_squared_weights[idx];
+                if n <= 1. {
+                    return 0.;
+                }
+
+                // Unbiased standard error of the mean
+                let standard_error = f64::sqrt(variance / (n - 1.));
+                // Keep dark pixels from dominating the estimate
+                standard_error / f64::max(mean, 1e-2)
+            })
+            .sum();
+
+        total / rendered as f64
+    }
+
+    /// Copy of the part of the film covered by `region`.
+    pub fn crop(&self, region: &Tile) -> Film {
+        let x0 = region.x0.min(self.width);
+        let y0 = region.y0.min(self.height);
+        let x1 = region.x1.clamp(x0, self.width);
+        let y1 = region.y1.clamp(y0, self.height);
+        let region = Tile { x0, y0, x1, y1 };
+        let indices: Vec<usize> = region.pixel_indices(self.width).collect();
+        let pick = |values: &[f64]| indices.iter().map(|&idx| values[idx]).collect();
+
+        Film {
+            width: region.width(),
+            height: region.height(),
+            passes: self.passes,
+            sum: indices.iter().map(|&idx| self.sum[idx]).collect(),
+            sum_weights: pick(&self.sum_weights),
+            sum_squared_luminance: pick(&self.sum_squared_luminance),
+            sum_absolute_weights: pick(&self.sum_absolute_weights),
+            sum_squared_weights: pick(&self.sum_squared_weights),
+        }
+    }
+
+    /// Writes the raw accumulation buffers, see `Checkpoint` for the surrounding file format.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&self.width.to_le_bytes())?;
+        writer.write_all(&self.height.to_le_bytes())?;
+        writer.write_all(&self.passes.to_le_bytes())?;
+
+        for idx in 0..self.sum.len() {
+            let values = [
+                self.sum[idx].x(),
+                self.sum[idx].y(),
+                self.sum[idx].z(),
+                self.sum_weights[idx],
+                self.sum_squared_luminance[idx],
+                self.sum_absolute_weights[idx],
+                self.sum_squared_weights[idx],
+            ];
+            for value in values {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn read_from<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let width = read_u32(reader)?;
+        let height = read_u32(reader)?;
+        let passes = read_u32(reader)?;
+
+        let mut film = Film::new(width, height);
+        film.passes = passes;
+
+        for idx in 0..film.sum.len() {
+            let r = read_f64(reader)?;
+            let g = read_f64(reader)?;
+            let b = read_f64(reader)?;
+            film.sum[idx] = Color::new(r, g, b);
+            film.sum_weights[idx] = read_f64(reader)?;
+            film.sum_squared_luminance[idx] = read_f64(reader)?;
+            film.sum_absolute_weights[idx] = read_f64(reader)?;
+            film.sum_squared_weights[idx] = read_f64(reader)?;
+        }
+
+        Ok(film)
+    }
+}
+
+pub(crate) fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+pub(crate) fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_f64<R: Read>(reader: &mut R) -> io::Result<f64> {
+    Ok(f64::from_bits(read_u64(reader)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pass(film: &Film, color: impl Fn(u32, u32) -> Color) -> Vec<FilmSample> {
+        (0..film.height())
+            .flat_map(|y| (0..film.width()).map(move |x| (x, y)))
+            .map(|(x, y)| FilmSample {
+                pixel: (x, y),
+                offset: (0., 0.),
+                color: color(x, y),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn noise_needs_two_passes() {
+        let mut film = Film::new(4, 3);
+        assert_eq!(film.noise(), f64::INFINITY);
+
+        let samples = pass(&film, |_, _| Color::new(0.5, 0.5, 0.5));
+        film.add_pass(&samples, &Filter::default(), &[]);
+        assert_eq!(film.noise(), f64::INFINITY);
+
+        film.add_pass(&samples, &Filter::default(), &[]);
+        assert_eq!(film.noise(), 0.);
+    }
+
+    #[test]
+    fn noise_shrinks_with_more_passes() {
+        let mut film = Film::new(4, 3);
+        let dark = pass(&film, |_, _| Color::new(0.2, 0.2, 0.2));
+        let bright = pass(&film, |_, _| Color::new(0.8, 0.8, 0.8));
+
+        film.add_pass(&dark, &Filter::default(), &[]);
+        film.add_pass(&bright, &Filter::default(), &[]);
+        let noise = film.noise();
+        assert!(noise > 0. && noise.is_finite());
+
+        for _ in 0..4 {
+            film.add_pass(&dark, &Filter::default(), &[]);
+            film.add_pass(&bright, &Filter::default(), &[]);
+        }
+        assert!(film.noise() < noise);
+        assert!((film.mean(0).luminance() - 0.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn pixels_without_samples_are_left_out_of_the_noise() {
+        let mut film = Film::new(4, 3);
+        let region = Tile {
+            x0: 1,
+            y0: 1,
+            x1: 3,
+            y1: 2,
+        };
+        let samples: Vec<FilmSample> = pass(&film, |x, _| Color::new(0.1, 0.1, 0.1) * x as f64)
+            .into_iter()
+            .filter(|sample| region.contains(sample.pixel))
+            .collect();
+        film.add_pass(&samples, &Filter::default(), &[]);
+        film.add_pass(&samples, &Filter::default(), &[]);
+        assert_eq!(film.noise(), 0.);
+    }
+
+    #[test]
+    fn box_splats_border_samples_into_their_own_pixel() {
+        let mut film = Film::new(3, 3);
+        let sample = FilmSample {
+            pixel: (1, 1),
+            offset: (-0.5, -0.5),
+            color: Color::new(1., 1., 1.),
+        };
+        film.add_pass(&[sample], &Filter::default(), &[]);
+        for idx in 0..9 {
+            let expected = if idx == 4 { 1. } else { 0. };
+            assert_eq!(film.mean(idx).luminance(), expected, "pixel {}", idx);
+        }
+    }
+
+    #[test]
+    fn splats_stay_within_their_sub_image() {
+        let mut film = Film::new(4, 1);
+        let left = Tile {
+            x0: 0,
+            y0: 0,
+            x1: 2,
+            y1: 1,
+        };
+        let right = Tile {
+            x0: 2,
+            x1: 4,
+            ..left
+        };
+        let sample = FilmSample {
+            pixel: (1, 0),
+            offset: (0.4, 0.),
+            color: Color::new(1., 1., 1.),
+        };
+        film.add_pass(&[sample], &Filter::Tent { radius: 2. }, &[left, right]);
+        assert!(film.mean(0).luminance() > 0. && film.mean(1).luminance() > 0.);
+        assert_eq!(film.mean(2).luminance(), 0.);
+        assert_eq!(film.mean(3).luminance(), 0.);
+    }
+}
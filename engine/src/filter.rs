@@ -0,0 +1,186 @@
+use serde::{Deserialize, Serialize};
+
+/// Pixel reconstruction filter. Every sample is splatted into all pixels whose center lies
+/// within `radius` (in pixels) of the sample, weighted by the separable filter function.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug)]
+#[serde(tag = "type")]
+pub enum Filter {
+    Box {
+        radius: f64,
+    },
+    Tent {
+        radius: f64,
+    },
+    Gaussian {
+        radius: f64,
+        /// Standard deviation in pixels
+        sigma: f64,
+    },
+    /// Mitchell-Netravali cubic, `b = c = 1/3` is the recommended setting
+    Mitchell {
+        radius: f64,
+        b: f64,
+        c: f64,
+    },
+    /// Sinc windowed by a wider sinc reaching zero at `radius`
+    Lanczos {
+        radius: f64,
+    },
+}
+
+impl Default for Filter {
+    /// A box with half a pixel radius only ever touches the pixel the sample was taken in.
+    fn default() -> Self {
+        Filter::Box { radius: 0.5 }
+    }
+}
+
+impl Filter {
+    pub fn radius(&self) -> f64 {
+        match *self {
+            Filter::Box { radius }
+            | Filter::Tent { radius }
+            | Filter::Gaussian { radius, .. }
+            | Filter::Mitchell { radius, .. }
+            | Filter::Lanczos { radius } => radius,
+        }
+    }
+
+    /// Weight of a sample at offset (x, y) in pixels from the pixel center.
+    pub fn evaluate(&self, x: f64, y: f64) -> f64 {
+        self.evaluate_1d(x) * self.evaluate_1d(y)
+    }
+
+    fn evaluate_1d(&self, x: f64) -> f64 {
+        let radius = self.radius();
+        // The box is half open, so a sample on the border between two pixels only counts in the
+        // one it was taken in, whose sample offsets lie in [-0.5, 0.5)
+        let outside = match self {
+            Filter::Box { .. } => x <= -radius || x > radius,
+            _ => x.abs() > radius,
+        };
+        if outside {
+            return 0.;
+        }
+        let x = x.abs();
+
+        match *self {
+            Filter::Box { .. } => 1.,
+            Filter::Tent { .. } => radius - x,
+            Filter::Gaussian { sigma, .. } => {
+                // Shifted down so the filter reaches zero at its radius
+                f64::max(0., gaussian(x, sigma) - gaussian(radius, sigma))
+            }
+            Filter::Mitchell { b, c, .. } => mitchell(2. * x / radius, b, c),
+            Filter::Lanczos { .. } => sinc(x) * sinc(x / radius),
+        }
+    }
+}
+
+fn gaussian(x: f64, sigma: f64) -> f64 {
+    f64::exp(-(x * x) / (2. * sigma * sigma))
+}
+
+// Mitchell-Netravali cubic over [0, 2].
+fn mitchell(x: f64, b: f64, c: f64) -> f64 {
+    if x > 1. {
+        ((-b - 6. * c) * x.powi(3)
+            + (6. * b + 30. * c) * x.powi(2)
+            + (-12. * b - 48. * c) * x
+            + (8. * b + 24. * c))
+            / 6.
+    } else {
+        ((12. - 9. * b - 6. * c) * x.powi(3)
+            + (-18. + 12. * b + 6. * c) * x.powi(2)
+            + (6. - 2. * b))
+            / 6.
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x < 1e-5 {
+        return 1.;
+    }
+    let pi_x = std::f64::consts::PI * x;
+    pi_x.sin() / pi_x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILTERS: [Filter; 5] = [
+        Filter::Box { radius: 0.5 },
+        Filter::Tent { radius: 1. },
+        Filter::Gaussian {
+            radius: 1.5,
+            sigma: 0.5,
+        },
+        Filter::Mitchell {
+            radius: 2.,
+            b: 1. / 3.,
+            c: 1. / 3.,
+        },
+        Filter::Lanczos { radius: 3. },
+    ];
+
+    #[test]
+    fn weights_vanish_outside_the_radius() {
+        for filter in FILTERS {
+            let radius = filter.radius();
+            assert!(filter.evaluate(0., 0.) > 0., "{:?}", filter);
+            for outside in [radius + 1e-9, radius + 0.5, 2. * radius + 1.] {
+                assert_eq!(filter.evaluate(outside, 0.), 0., "{:?}", filter);
+                assert_eq!(filter.evaluate(-outside, 0.), 0., "{:?}", filter);
+                assert_eq!(filter.evaluate(0., outside), 0., "{:?}", filter);
+            }
+        }
+    }
+
+    #[test]
+    fn weights_are_continuous_at_the_radius() {
+        // All but the box reach zero at their radius, so a sample doesn't pop in and out
+        for filter in &FILTERS[1..] {
+            let radius = filter.radius();
+            assert!(
+                filter.evaluate(radius - 1e-9, 0.).abs() < 1e-6,
+                "{:?}",
+                filter
+            );
+        }
+    }
+
+    #[test]
+    fn weights_are_symmetric_and_separable() {
+        for filter in FILTERS {
+            for x in [0.1, 0.3, 0.45] {
+                let weight = filter.evaluate(x, 0.2);
+                assert!((weight - filter.evaluate(-x, -0.2)).abs() < 1e-12);
+
+                let separable = filter.evaluate(x, 0.) * filter.evaluate(0., 0.2);
+                let center = filter.evaluate(0., 0.);
+                assert!((weight * center - separable).abs() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn box_counts_a_border_sample_in_one_pixel() {
+        let filter = Filter::default();
+        // A sample at offset -0.5 lies on the left border of its pixel and the right border of
+        // the one to the left of it
+        assert_eq!(filter.evaluate(0.5, 0.), 1.);
+        assert_eq!(filter.evaluate(-0.5, 0.), 0.);
+    }
+
+    #[test]
+    fn sharpening_filters_have_negative_lobes() {
+        let mitchell = Filter::Mitchell {
+            radius: 2.,
+            b: 1. / 3.,
+            c: 1. / 3.,
+        };
+        assert!(mitchell.evaluate(1.5, 0.) < 0.);
+        assert!(Filter::Lanczos { radius: 3. }.evaluate(1.5, 0.) < 0.);
+    }
+}
@@ -0,0 +1,47 @@
+use math::interval::Interval;
+use serde::{Deserialize, Serialize};
+
+use crate::{medium::Medium, ray::Ray};
+
+use super::{
+    hittable::{HitRecord, Hittable},
+    object::HittableObject,
+};
+
+/// Fills a closed boundary object with a participating medium. The integrator enters the medium
+/// when a ray crosses the boundary from the outside and leaves it when it crosses back.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConstantMedium {
+    pub boundary: Box<HittableObject>,
+    pub medium: Medium,
+    /// Whether the material of the boundary is rendered as a surface, like glass around a
+    /// subsurface volume. Otherwise rays cross the boundary unaffected, like for fog or smoke.
+    #[serde(default)]
+    pub surface: bool,
+}
+
+impl Hittable for ConstantMedium {
+    fn hit<'a>(&'a self, r: &Ray, ray_t: Interval, rec: &mut HitRecord<'a>) -> bool {
+        if !self.boundary.hit(r, ray_t, rec) {
+            return false;
+        }
+
+        rec.medium = Some(MediumBoundary {
+            medium: self.medium,
+            surface: self.surface,
+        });
+
+        true
+    }
+
+    fn assign_object_ids(&mut self, next_id: &mut usize) {
+        self.boundary.assign_object_ids(next_id);
+    }
+}
+
+/// The medium on the inside of a surface that was hit.
+#[derive(Debug, Clone, Copy)]
+pub struct MediumBoundary {
+    pub medium: Medium,
+    pub surface: bool,
+}
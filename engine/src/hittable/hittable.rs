@@ -1,17 +1,64 @@
-use math::{interval::Interval, vec3::Vec3};
+use math::{
+    interval::Interval,
+    vec3::{Color, Point3, Vec3},
+};
 
-use crate::{material::object::MaterialObject, ray::Ray};
+use crate::{
+    material::{lambertian::Lambertian, object::MaterialObject},
+    ray::Ray,
+};
 
-#[derive(Clone)]
-pub struct HitRecord {
+use super::constant_medium::MediumBoundary;
+
+#[derive(Clone, Copy)]
+pub struct HitRecord<'a> {
     pub t: f64,
     pub p: Vec3,
-    pub material: MaterialObject,
+    pub material: &'a MaterialObject,
+    /// Identifies the primitive that was hit, a path leaves a dielectric through the object it
+    /// entered it by. 0 until the world's ids are assigned.
+    pub object_id: usize,
     pub normal: Vec3,
     pub front_face: bool,
+    /// Surface coordinates of the hit point, textures are mapped onto them
+    pub u: f64,
+    pub v: f64,
+    /// Partial derivatives of the hit point along the surface coordinates, `dpdu` is the
+    /// tangent normal maps are oriented by
+    pub dpdu: Vec3,
+    pub dpdv: Vec3,
+    /// Set if the surface encloses a participating medium
+    pub medium: Option<MediumBoundary>,
+    /// Index of refraction around the object at the hit point, filled in by the integrator from
+    /// the dielectrics the path is inside of
+    pub outside_refraction_index: f64,
+}
+
+// Material of a record that nothing was hit for yet
+static NO_MATERIAL: MaterialObject = MaterialObject::Lambertian(Lambertian {
+    albedo: Color::zero(),
+});
+
+impl Default for HitRecord<'_> {
+    fn default() -> Self {
+        Self {
+            t: 0.,
+            p: Point3::zero(),
+            material: &NO_MATERIAL,
+            object_id: 0,
+            normal: Vec3::zero(),
+            front_face: false,
+            u: 0.,
+            v: 0.,
+            dpdu: Vec3::zero(),
+            dpdv: Vec3::zero(),
+            medium: None,
+            outside_refraction_index: 1.,
+        }
+    }
 }
 
-impl HitRecord {
+impl HitRecord<'_> {
     pub fn set_face_normal(&mut self, r: &Ray, outward_normal: Vec3) {
         // Sets the hit record normal vector.
         // NOTE: the parameter `outwawrd_normal` is assumed to have unit length.
@@ -25,5 +72,8 @@ impl HitRecord {
 }
 
 pub trait Hittable {
-    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool;
+    fn hit<'a>(&'a self, r: &Ray, ray_t: Interval, rec: &mut HitRecord<'a>) -> bool;
+
+    /// Numbers the primitives from `next_id` on, they report it as `HitRecord::object_id`.
+    fn assign_object_ids(&mut self, next_id: &mut usize);
 }
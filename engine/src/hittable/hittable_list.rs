@@ -13,6 +13,12 @@ pub struct HittableList {
     pub objects: Vec<HittableObject>,
 }
 
+impl Default for HittableList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl HittableList {
     pub fn new() -> Self {
         Self {
@@ -26,7 +32,7 @@ impl HittableList {
 }
 
 impl Hittable for HittableList {
-    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+    fn hit<'a>(&'a self, r: &Ray, ray_t: Interval, rec: &mut HitRecord<'a>) -> bool {
         let temp_rec: Option<HitRecord> = None;
         let mut hit_anything = false;
         let mut closest_so_far = ray_t.max;
@@ -35,18 +41,79 @@ impl Hittable for HittableList {
             if object.hit(r, Interval::new(ray_t.min, closest_so_far), rec) {
                 hit_anything = true;
                 closest_so_far = rec.t;
-                match &temp_rec {
-                    Some(temp_rec) => {
-                        rec.t = temp_rec.t;
-                        rec.p = temp_rec.p;
-                        rec.normal = temp_rec.normal;
-                        rec.front_face = temp_rec.front_face;
-                    }
-                    None => {}
+                if let Some(temp_rec) = &temp_rec {
+                    rec.t = temp_rec.t;
+                    rec.p = temp_rec.p;
+                    rec.normal = temp_rec.normal;
+                    rec.front_face = temp_rec.front_face;
                 }
             }
         }
 
-        return hit_anything;
+        hit_anything
+    }
+
+    fn assign_object_ids(&mut self, next_id: &mut usize) {
+        for object in self.objects.iter_mut() {
+            object.assign_object_ids(next_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use math::vec3::{Color, Point3, Vec3};
+
+    use super::*;
+    use crate::{
+        animation::AnimatedVec3,
+        hittable::{constant_medium::ConstantMedium, primitives::sphere::Sphere},
+        material::{lambertian::Lambertian, object::MaterialObject},
+        medium::Medium,
+    };
+
+    fn sphere(x: f64) -> HittableObject {
+        HittableObject::Sphere(Box::new(Sphere::new(
+            AnimatedVec3::static_value(Point3::new(x, 0., 0.)),
+            0.5,
+            MaterialObject::Lambertian(Lambertian {
+                albedo: Color::one(),
+            }),
+        )))
+    }
+
+    fn id_at(world: &HittableList, x: f64) -> usize {
+        let ray = Ray::new(Point3::new(x, 0., 5.), Vec3::new(0., 0., -1.));
+        let mut rec = HitRecord::default();
+        assert!(world.hit(&ray, Interval::new(0.001, f64::INFINITY), &mut rec));
+        rec.object_id
+    }
+
+    #[test]
+    fn every_primitive_gets_its_own_id() {
+        let mut nested = HittableList::new();
+        nested.add(sphere(2.));
+        nested.add(HittableObject::ConstantMedium(ConstantMedium {
+            boundary: Box::new(sphere(4.)),
+            medium: Medium {
+                density: 1.,
+                albedo: Color::one(),
+                phase: Default::default(),
+            },
+            surface: true,
+        }));
+
+        let mut world = HittableList::new();
+        world.add(sphere(0.));
+        world.add(HittableObject::List(nested));
+        world.add(sphere(6.));
+        world.assign_object_ids(&mut 1);
+
+        let ids: Vec<usize> = [0., 2., 4., 6.].iter().map(|x| id_at(&world, *x)).collect();
+        assert_eq!(ids, [1, 2, 3, 4]);
+
+        // The ids don't depend on where the world lives in memory
+        let moved = Box::new(world.clone());
+        assert_eq!(id_at(&moved, 4.), 3);
     }
 }
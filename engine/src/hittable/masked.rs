@@ -0,0 +1,165 @@
+use math::{interval::Interval, random::mix_seed};
+use serde::{Deserialize, Serialize};
+
+use crate::{ray::Ray, texture::Texture};
+
+use super::{
+    hittable::{HitRecord, Hittable},
+    object::HittableObject,
+};
+
+/// Cuts holes into another object with an opacity texture, like leaves, fences or decals. Rays
+/// pass through the transparent parts as if the surface wasn't there.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Masked {
+    pub object: Box<HittableObject>,
+    /// Opacity over the surface, 0 is fully transparent and 1 fully opaque
+    pub opacity: Texture<f64>,
+    /// Hits with a lower opacity are ignored
+    #[serde(default = "half")]
+    pub threshold: f64,
+    /// Lets rays pass with a probability of one minus the opacity instead of cutting at the
+    /// threshold, for partially transparent surfaces
+    #[serde(default)]
+    pub stochastic: bool,
+}
+
+fn half() -> f64 {
+    0.5
+}
+
+impl Masked {
+    fn is_opaque(&self, r: &Ray, rec: &HitRecord) -> bool {
+        let opacity = self.opacity.value(rec);
+        if !self.stochastic {
+            return opacity >= self.threshold;
+        }
+
+        // Hits have no sampler to draw from, the ray and the hit distance are hashed into a
+        // uniform number instead, so the same ray always makes the same decision
+        let origin = r.origin();
+        let direction = r.direction();
+        let hash = [
+            origin.x(),
+            origin.y(),
+            origin.z(),
+            direction.x(),
+            direction.y(),
+            direction.z(),
+            rec.t,
+        ]
+        .iter()
+        .fold(0, |hash, value| mix_seed(hash, value.to_bits()));
+        let u = (hash >> 11) as f64 / (1u64 << 53) as f64;
+
+        u < opacity
+    }
+}
+
+impl Hittable for Masked {
+    fn hit<'a>(&'a self, r: &Ray, ray_t: Interval, rec: &mut HitRecord<'a>) -> bool {
+        // Only overwrite the record with an opaque hit, it may hold the closest hit so far
+        let mut temp_rec = HitRecord::default();
+        let mut t_min = ray_t.min;
+
+        while self
+            .object
+            .hit(r, Interval::new(t_min, ray_t.max), &mut temp_rec)
+        {
+            if self.is_opaque(r, &temp_rec) {
+                *rec = temp_rec;
+                return true;
+            }
+            // Continue right behind the transparent hit
+            t_min = temp_rec.t + 1e-6;
+        }
+
+        false
+    }
+
+    fn assign_object_ids(&mut self, next_id: &mut usize) {
+        self.object.assign_object_ids(next_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use math::vec3::{Color, Point3, Vec3};
+
+    use super::*;
+    use crate::{
+        animation::AnimatedVec3,
+        hittable::{hittable_list::HittableList, primitives::sphere::Sphere},
+        material::{lambertian::Lambertian, object::MaterialObject},
+    };
+
+    fn sphere(z: f64) -> HittableObject {
+        HittableObject::Sphere(Box::new(Sphere::new(
+            AnimatedVec3::static_value(Point3::new(0., 0., z)),
+            0.5,
+            MaterialObject::Lambertian(Lambertian {
+                albedo: Color::one(),
+            }),
+        )))
+    }
+
+    fn masked(z: f64, opacity: f64, stochastic: bool) -> HittableObject {
+        HittableObject::Masked(Masked {
+            object: Box::new(sphere(z)),
+            opacity: Texture::Constant(opacity),
+            threshold: 0.5,
+            stochastic,
+        })
+    }
+
+    // Distance to the closest hit of a ray from `x` on the z axis looking down it
+    fn hit_distance(world: &HittableList, x: f64) -> Option<f64> {
+        let ray = Ray::new(Point3::new(x, 0., 10.), Vec3::new(0., 0., -1.));
+        let mut rec = HitRecord::default();
+        world
+            .hit(&ray, Interval::new(0.001, f64::INFINITY), &mut rec)
+            .then_some(rec.t)
+    }
+
+    #[test]
+    fn cutout_at_the_threshold() {
+        let mut world = HittableList::new();
+        world.add(masked(2., 0.3, false));
+        world.add(sphere(0.));
+        assert_eq!(hit_distance(&world, 0.), Some(9.5));
+
+        let mut world = HittableList::new();
+        world.add(masked(2., 0.5, false));
+        world.add(sphere(0.));
+        assert_eq!(hit_distance(&world, 0.), Some(7.5));
+    }
+
+    #[test]
+    fn transparent_hits_keep_the_closest_hit() {
+        // The opaque sphere is hit first, the transparent one behind it must not erase it
+        let mut world = HittableList::new();
+        world.add(sphere(2.));
+        world.add(masked(0., 0., false));
+        assert_eq!(hit_distance(&world, 0.), Some(7.5));
+    }
+
+    #[test]
+    fn stochastic_transparency_lets_the_transparent_part_through() {
+        let mut world = HittableList::new();
+        world.add(masked(0., 0.25, true));
+
+        let offsets: Vec<f64> = (0..2000).map(|i| (i as f64 / 2000. - 0.5) * 0.5).collect();
+        let hits = offsets
+            .iter()
+            .filter(|x| hit_distance(&world, **x).is_some())
+            .count();
+        // Rays pass the front and the back of the sphere, each with three quarters probability
+        let expected = 1. - 0.75 * 0.75;
+        assert!((hits as f64 / offsets.len() as f64 - expected).abs() < 0.05);
+
+        // The same ray always makes the same decision
+        for x in &offsets[..50] {
+            assert_eq!(hit_distance(&world, *x), hit_distance(&world, *x));
+        }
+    }
+}
@@ -1,4 +1,7 @@
+pub mod constant_medium;
+#[allow(clippy::module_inception)]
 pub mod hittable;
 pub mod hittable_list;
+pub mod masked;
+pub mod object;
 pub mod primitives;
-pub mod object;
\ No newline at end of file
@@ -4,23 +4,40 @@ use serde::{Deserialize, Serialize};
 use crate::ray::Ray;
 
 use super::{
+    constant_medium::ConstantMedium,
     hittable::{HitRecord, Hittable},
     hittable_list::HittableList,
+    masked::Masked,
     primitives::sphere::Sphere,
 };
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum HittableObject {
-    Sphere(Sphere),
+    Sphere(Box<Sphere>),
     List(HittableList),
+    ConstantMedium(ConstantMedium),
+    Masked(Masked),
 }
 
 impl Hittable for HittableObject {
-    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+    fn hit<'a>(&'a self, r: &Ray, ray_t: Interval, rec: &mut HitRecord<'a>) -> bool {
         match self {
             HittableObject::Sphere(sphere) => sphere.hit(r, ray_t, rec),
             HittableObject::List(list) => list.hit(r, ray_t, rec),
+            HittableObject::ConstantMedium(constant_medium) => constant_medium.hit(r, ray_t, rec),
+            HittableObject::Masked(masked) => masked.hit(r, ray_t, rec),
+        }
+    }
+
+    fn assign_object_ids(&mut self, next_id: &mut usize) {
+        match self {
+            HittableObject::Sphere(sphere) => sphere.assign_object_ids(next_id),
+            HittableObject::List(list) => list.assign_object_ids(next_id),
+            HittableObject::ConstantMedium(constant_medium) => {
+                constant_medium.assign_object_ids(next_id)
+            }
+            HittableObject::Masked(masked) => masked.assign_object_ids(next_id),
         }
     }
 }
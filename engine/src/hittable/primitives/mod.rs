@@ -1 +1 @@
-pub mod sphere;
\ No newline at end of file
+pub mod sphere;
@@ -1,3 +1,5 @@
+use std::f64::consts::PI;
+
 use math::{interval::Interval, max::max_f64, vec3::Vec3};
 use serde::{Deserialize, Serialize};
 
@@ -8,12 +10,14 @@ use crate::{
     ray::Ray,
 };
 
-#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Sphere {
     center: AnimatedVec3,
     radius: f64,
 
     material: MaterialObject,
+    #[serde(skip)]
+    id: usize,
 }
 
 impl Sphere {
@@ -22,12 +26,36 @@ impl Sphere {
             center,
             radius: max_f64(radius, 0.),
             material,
+            id: 0,
+        }
+    }
+}
+
+impl Sphere {
+    // Latitude and longitude of the hit point, u goes around the y axis starting at -x and v from
+    // the bottom to the top.
+    fn set_surface_coordinates(&self, outward_normal: Vec3, rec: &mut HitRecord) {
+        let (x, y, z) = (outward_normal.x(), outward_normal.y(), outward_normal.z());
+        let theta = f64::acos(-y);
+        let phi = f64::atan2(-z, x) + PI;
+
+        rec.u = phi / (2. * PI);
+        rec.v = theta / PI;
+
+        let sin_theta = f64::sqrt(x * x + z * z);
+        if sin_theta < 1e-8 {
+            // The poles, any tangent will do
+            rec.dpdu = Vec3::new(2. * PI * self.radius, 0., 0.);
+            rec.dpdv = Vec3::new(0., 0., PI * self.radius);
+            return;
         }
+        rec.dpdu = 2. * PI * self.radius * Vec3::new(z, 0., -x);
+        rec.dpdv = PI * self.radius * Vec3::new(-x * y / sin_theta, sin_theta, -y * z / sin_theta);
     }
 }
 
 impl Hittable for Sphere {
-    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+    fn hit<'a>(&'a self, r: &Ray, ray_t: Interval, rec: &mut HitRecord<'a>) -> bool {
         let current_center = self.center.value_at_time(r.time());
         let oc = current_center - r.origin();
         let a = r.direction().length_squared();
@@ -55,8 +83,16 @@ impl Hittable for Sphere {
         rec.p = r.at(rec.t);
         let outward_normal = (rec.p - current_center) / self.radius;
         rec.set_face_normal(r, outward_normal);
-        rec.material = self.material;
+        self.set_surface_coordinates(outward_normal, rec);
+        rec.material = &self.material;
+        rec.object_id = self.id;
+        rec.medium = None;
+
+        true
+    }
 
-        return true;
+    fn assign_object_ids(&mut self, next_id: &mut usize) {
+        self.id = *next_id;
+        *next_id += 1;
     }
 }
@@ -0,0 +1,645 @@
+use std::{f64::consts::PI, sync::Arc};
+
+use math::{
+    circle::degrees_to_radians,
+    vec3::{Point3, Vec3},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    material::dispersion::{refraction_index_at, Dispersion},
+    texture::ImageTexture,
+};
+
+/// Wavelength in nanometers at which the chromatic aberration vanishes, and the distance from it
+/// at which the aberration coefficients apply.
+const ABERRATION_CENTER_WAVELENGTH: f64 = 550.;
+const ABERRATION_WAVELENGTH_STEP: f64 = 100.;
+
+/// Imperfections of the camera lens, on top of the thin lens blur set up by
+/// `defocus_angle_in_degrees` and `focus_dist`.
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct LensConfig {
+    /// Shape of the opening out of focus highlights take
+    pub aperture: Aperture,
+    /// Clips the aperture towards the edges of the image the way the lens barrel does, which
+    /// squeezes out of focus highlights into cat's eyes and darkens the corners. 0 disables it,
+    /// at 1 no light reaches the corners.
+    pub cat_eye: f64,
+    /// Relative change of the image size per 100 nm of wavelength above 550 nm, colors fringes
+    /// towards the edges of the image. Only applies in spectral mode.
+    pub lateral_chromatic_aberration: f64,
+    /// Relative change of the focus distance per 100 nm of wavelength above 550 nm, colors the
+    /// out of focus parts of the image. Only applies in spectral mode.
+    pub longitudinal_chromatic_aberration: f64,
+}
+
+impl LensConfig {
+    pub fn is_chromatic(&self) -> bool {
+        self.lateral_chromatic_aberration != 0. || self.longitudinal_chromatic_aberration != 0.
+    }
+
+    /// Factors the image size and the focus distance are scaled with at `wavelength`.
+    pub fn chromatic_aberration(&self, wavelength: f64) -> (f64, f64) {
+        let t = (wavelength - ABERRATION_CENTER_WAVELENGTH) / ABERRATION_WAVELENGTH_STEP;
+        (
+            1. + self.lateral_chromatic_aberration * t,
+            1. + self.longitudinal_chromatic_aberration * t,
+        )
+    }
+
+    /// Whether the lens barrel blocks the point `lens_sample` of the aperture for the image
+    /// position `x`, `y`, which is measured from the image center with x to the right and y up
+    /// in half image diagonals.
+    pub fn is_vignetted(&self, lens_sample: (f64, f64), x: f64, y: f64) -> bool {
+        if self.cat_eye <= 0. {
+            return false;
+        }
+
+        // The barrel is seen as a second circle moving across the aperture
+        let dx = lens_sample.0 - 2. * self.cat_eye * x;
+        let dy = lens_sample.1 - 2. * self.cat_eye * y;
+        dx * dx + dy * dy > 1.
+    }
+}
+
+/// Shape of the lens opening, scaled to fit the unit circle.
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+#[serde(tag = "type")]
+pub enum Aperture {
+    #[default]
+    Circle,
+    /// Regular polygon formed by `blades` straight aperture blades
+    Polygon {
+        blades: u32,
+        #[serde(default)]
+        rotation_in_degrees: f64,
+    },
+    /// Opening painted into an image, brighter texels let through more light. The image fills
+    /// the square around the unit circle along its longer side.
+    Image(ApertureImage),
+}
+
+impl Aperture {
+    /// Maps a point of the unit square uniformly onto the opening.
+    pub fn sample(&self, u: f64, v: f64) -> (f64, f64) {
+        match self {
+            Aperture::Circle => {
+                let p = Vec3::in_unit_disk_from_sample(u, v);
+                (p.x(), p.y())
+            }
+            Aperture::Polygon {
+                blades,
+                rotation_in_degrees,
+            } => {
+                let blades = (*blades).max(3) as f64;
+                let scaled = u * blades;
+                let blade = f64::min(scaled.floor(), blades - 1.);
+                let angle = |corner: f64| {
+                    degrees_to_radians(*rotation_in_degrees) + 2. * PI * corner / blades
+                };
+                let (a0, a1) = (angle(blade), angle(blade + 1.));
+
+                // Uniform point in the triangle between the center and the edge of the blade
+                let r = f64::sqrt(scaled - blade);
+                (
+                    r * ((1. - v) * a0.cos() + v * a1.cos()),
+                    r * ((1. - v) * a0.sin() + v * a1.sin()),
+                )
+            }
+            Aperture::Image(image) => image.sample(u, v),
+        }
+    }
+
+    /// Whether the point `x`, `y` lies inside the opening.
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        match self {
+            Aperture::Circle => x * x + y * y <= 1.,
+            Aperture::Polygon {
+                blades,
+                rotation_in_degrees,
+            } => {
+                let blades = (*blades).max(3) as f64;
+                let rotation = degrees_to_radians(*rotation_in_degrees);
+                let sector = 2. * PI / blades;
+                let blade = ((y.atan2(x) - rotation).rem_euclid(2. * PI) / sector).floor();
+
+                // Distance from the center along the normal of the blade's edge
+                let middle = rotation + (blade + 0.5) * sector;
+                x * middle.cos() + y * middle.sin() <= (sector / 2.).cos()
+            }
+            Aperture::Image(image) => image.contains(x, y),
+        }
+    }
+}
+
+/// Aperture image together with the distribution its openings are sampled with.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(try_from = "ImageTexture", into = "ImageTexture")]
+pub struct ApertureImage {
+    image: ImageTexture,
+    // Running sum of the texel luminances row by row from the top, normalized to end at 1
+    cdf: Arc<Vec<f64>>,
+}
+
+impl TryFrom<ImageTexture> for ApertureImage {
+    type Error = String;
+
+    fn try_from(image: ImageTexture) -> Result<Self, Self::Error> {
+        let mut total = 0.;
+        let mut cdf = Vec::with_capacity((image.width() * image.height()) as usize);
+        for y in 0..image.height() {
+            for x in 0..image.width() {
+                total += f64::max(image.texel(x, y).luminance(), 0.);
+                cdf.push(total);
+            }
+        }
+        if total <= 0. {
+            return Err(format!("Aperture image {} has no opening", image.path()));
+        }
+        cdf.iter_mut().for_each(|c| *c /= total);
+
+        Ok(Self {
+            image,
+            cdf: Arc::new(cdf),
+        })
+    }
+}
+
+impl From<ApertureImage> for ImageTexture {
+    fn from(aperture: ApertureImage) -> Self {
+        aperture.image
+    }
+}
+
+impl ApertureImage {
+    fn sample(&self, u: f64, v: f64) -> (f64, f64) {
+        let index = self
+            .cdf
+            .partition_point(|&c| c <= u)
+            .min(self.cdf.len() - 1);
+        let lower = if index == 0 { 0. } else { self.cdf[index - 1] };
+        // Where in its texel the sample lands, reusing what is left of `u` horizontally
+        let jitter = ((u - lower) / (self.cdf[index] - lower)).clamp(0., 1.);
+
+        let width = self.image.width() as usize;
+        let x = (index % width) as f64 + jitter;
+        let y = (index / width) as f64 + v;
+        self.image_to_aperture(x, y)
+    }
+
+    fn contains(&self, x: f64, y: f64) -> bool {
+        let (x, y) = self.aperture_to_image(x, y);
+        if x < 0. || y < 0. {
+            return false;
+        }
+        let (x, y) = (x as u32, y as u32);
+        x < self.image.width()
+            && y < self.image.height()
+            && self.image.texel(x, y).luminance() >= 0.5
+    }
+
+    // Texel coordinates from the top left to the unit square with y up, and back.
+    fn image_to_aperture(&self, x: f64, y: f64) -> (f64, f64) {
+        let (width, height) = (self.image.width() as f64, self.image.height() as f64);
+        let scale = width.max(height);
+        ((2. * x - width) / scale, (height - 2. * y) / scale)
+    }
+
+    fn aperture_to_image(&self, x: f64, y: f64) -> (f64, f64) {
+        let (width, height) = (self.image.width() as f64, self.image.height() as f64);
+        let scale = width.max(height);
+        ((x * scale + width) / 2., (height - y * scale) / 2.)
+    }
+}
+
+/// Lens made of spherical glass elements the camera rays are traced through, from the film out
+/// into the scene (Kolb et al., "A Realistic Camera Model for Computer Graphics"). Lengths are
+/// in millimeters.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct LensSystem {
+    /// Interfaces from the front of the lens, facing the scene, to the back
+    pub elements: Vec<LensElement>,
+    #[serde(default = "full_frame_diagonal")]
+    pub film_diagonal_in_mm: f64,
+    #[serde(default = "meters_per_millimeter")]
+    pub world_units_per_mm: f64,
+}
+
+fn full_frame_diagonal() -> f64 {
+    43.27
+}
+
+fn meters_per_millimeter() -> f64 {
+    0.001
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug)]
+pub struct LensElement {
+    /// Radius of the spherical interface, positive when it bulges towards the scene. 0 marks
+    /// the aperture stop, which takes the shape of the camera's aperture.
+    pub curvature_radius: f64,
+    /// Distance along the axis to the next interface, the film behind the last one is placed to
+    /// bring `focus_dist` into focus instead
+    pub thickness: f64,
+    /// Index of refraction behind the interface, 1 for air
+    #[serde(default = "one")]
+    pub refraction_index: f64,
+    #[serde(default)]
+    pub dispersion: Option<Dispersion>,
+    pub aperture_diameter: f64,
+}
+
+fn one() -> f64 {
+    1.
+}
+
+/// Placement of the film behind a lens system.
+#[derive(Debug, Clone, Copy)]
+pub struct LensFocus {
+    /// Distance from the film to the last interface
+    film_distance: f64,
+    film_width: f64,
+    film_height: f64,
+    /// Makes up for the light the lens blocks at the center of the image
+    pub exposure: f64,
+}
+
+impl LensSystem {
+    pub fn is_dispersive(&self) -> bool {
+        self.elements
+            .iter()
+            .any(|element| element.dispersion.is_some())
+    }
+
+    /// Places the film so objects `focus_distance` world units in front of it are in focus,
+    /// using the thick lens approximation of the system. Fails for systems that don't form an
+    /// image.
+    pub fn focus(
+        &self,
+        focus_distance: f64,
+        aspect_ratio: f64,
+        aperture: &Aperture,
+    ) -> Result<LensFocus, String> {
+        // Parallel rays close to the axis, through a round stop so any opening works
+        let height = 0.001 * self.film_diagonal_in_mm;
+        let front = self.interface_positions(0.)[0];
+        let (rear_focal_point, rear_principal_plane) = Self::cardinal_points(
+            height,
+            self.trace(
+                Point3::new(height, 0., front - 1.),
+                Vec3::new(0., 0., 1.),
+                0.,
+                None,
+                &Aperture::Circle,
+                false,
+            ),
+        )?;
+        let (_, front_principal_plane) = Self::cardinal_points(
+            height,
+            self.trace(
+                Point3::new(height, 0., 1.),
+                Vec3::new(0., 0., -1.),
+                0.,
+                None,
+                &Aperture::Circle,
+                true,
+            ),
+        )?;
+        let focal_length = rear_focal_point - rear_principal_plane;
+        if focal_length <= 0. {
+            return Err("The lens system does not converge light onto the film".to_string());
+        }
+
+        // Thin lens equation between the principal planes, solved for the image distance
+        let distance = focus_distance / self.world_units_per_mm;
+        let l = distance + front_principal_plane - rear_principal_plane;
+        let discriminant = l * l - 4. * focal_length * l;
+        if discriminant < 0. {
+            // The object and the image are at least four focal lengths apart
+            let closest = 4. * focal_length - front_principal_plane + rear_principal_plane;
+            return Err(format!(
+                "The lens system can't focus closer than {} world units, the focus distance is {}",
+                closest * self.world_units_per_mm,
+                focus_distance
+            ));
+        }
+        let image_distance = (l - discriminant.sqrt()) / 2.;
+        let film_distance = image_distance + rear_principal_plane;
+
+        let film_width =
+            self.film_diagonal_in_mm * aspect_ratio / f64::sqrt(1. + aspect_ratio * aspect_ratio);
+        let mut focus = LensFocus {
+            film_distance,
+            film_width,
+            film_height: film_width / aspect_ratio,
+            exposure: 1.,
+        };
+
+        // Share of the rays from the center of the film that make it through
+        let steps = 32;
+        let passed = (0..steps * steps)
+            .filter(|step| {
+                let u = (step % steps) as f64 + 0.5;
+                let v = (step / steps) as f64 + 0.5;
+                let p = Vec3::in_unit_disk_from_sample(u / steps as f64, v / steps as f64);
+                self.ray(&focus, 0.5, 0.5, (p.x(), p.y()), None, aperture)
+                    .is_some()
+            })
+            .count();
+        if passed == 0 {
+            return Err("No light passes through the lens system".to_string());
+        }
+        focus.exposure = (steps * steps) as f64 / passed as f64;
+
+        Ok(focus)
+    }
+
+    /// Ray from the image position `s`, `t` in [0, 1] from the top left through the point
+    /// `lens_sample` of the unit disk on the last element, out into the scene. Its origin and
+    /// direction are in camera space with x to the right, y up and z pointing backwards, `None`
+    /// if the lens blocks it.
+    pub fn ray(
+        &self,
+        focus: &LensFocus,
+        s: f64,
+        t: f64,
+        lens_sample: (f64, f64),
+        wavelength: Option<f64>,
+        aperture: &Aperture,
+    ) -> Option<(Point3, Vec3)> {
+        // The lens flips the image, the top left of the image is at the bottom right of the film
+        let film_point = Point3::new(
+            (0.5 - s) * focus.film_width,
+            (t - 0.5) * focus.film_height,
+            0.,
+        );
+        let rear_radius = self.elements.last()?.aperture_diameter / 2.;
+        let rear_point = Point3::new(
+            lens_sample.0 * rear_radius,
+            lens_sample.1 * rear_radius,
+            -focus.film_distance,
+        );
+
+        let (origin, direction) = self.trace(
+            film_point,
+            rear_point - film_point,
+            focus.film_distance,
+            wavelength,
+            aperture,
+            true,
+        )?;
+        Some((origin * self.world_units_per_mm, direction))
+    }
+
+    // Axial positions of the interfaces, with the film at 0 and the scene towards negative z.
+    fn interface_positions(&self, film_distance: f64) -> Vec<f64> {
+        let mut z = -film_distance;
+        let mut positions = vec![0.; self.elements.len()];
+        for i in (0..self.elements.len()).rev() {
+            positions[i] = z;
+            if i > 0 {
+                z -= self.elements[i - 1].thickness;
+            }
+        }
+        positions
+    }
+
+    fn refraction_index(&self, element: Option<&LensElement>, wavelength: Option<f64>) -> f64 {
+        element.map_or(1., |element| {
+            refraction_index_at(element.refraction_index, element.dispersion, wavelength)
+        })
+    }
+
+    // Refracts a ray through all interfaces, towards the scene or towards the film.
+    fn trace(
+        &self,
+        mut origin: Point3,
+        direction: Vec3,
+        film_distance: f64,
+        wavelength: Option<f64>,
+        aperture: &Aperture,
+        towards_scene: bool,
+    ) -> Option<(Point3, Vec3)> {
+        let positions = self.interface_positions(film_distance);
+        let mut direction = direction.unit_vector();
+
+        let order: Vec<usize> = if towards_scene {
+            (0..self.elements.len()).rev().collect()
+        } else {
+            (0..self.elements.len()).collect()
+        };
+        for i in order {
+            let element = &self.elements[i];
+            let z = positions[i];
+            let radius = element.aperture_diameter / 2.;
+
+            if element.curvature_radius == 0. {
+                if direction.z() == 0. {
+                    return None;
+                }
+                origin = origin + (z - origin.z()) / direction.z() * direction;
+                if !aperture.contains(origin.x() / radius, origin.y() / radius) {
+                    return None;
+                }
+                continue;
+            }
+
+            let center = Point3::new(0., 0., z + element.curvature_radius);
+            let oc = origin - center;
+            let b = oc.dot(&direction);
+            let c = oc.length_squared() - element.curvature_radius * element.curvature_radius;
+            let discriminant = b * b - c;
+            if discriminant < 0. {
+                return None;
+            }
+            // The interface is the half of the sphere facing its vertex
+            let t = if (direction.z() > 0.) != (element.curvature_radius < 0.) {
+                -b - discriminant.sqrt()
+            } else {
+                -b + discriminant.sqrt()
+            };
+            if t < 0. {
+                return None;
+            }
+            origin = origin + t * direction;
+            if origin.x() * origin.x() + origin.y() * origin.y() > radius * radius {
+                return None;
+            }
+
+            let mut normal = (origin - center).unit_vector();
+            if normal.dot(&direction) > 0. {
+                normal = normal.inverted();
+            }
+            let behind = self.refraction_index(Some(element), wavelength);
+            let front =
+                self.refraction_index(i.checked_sub(1).map(|i| &self.elements[i]), wavelength);
+            let ratio = if towards_scene {
+                behind / front
+            } else {
+                front / behind
+            };
+
+            let cos_i = -direction.dot(&normal);
+            let sin2_t = ratio * ratio * (1. - cos_i * cos_i);
+            if sin2_t > 1. {
+                return None;
+            }
+            direction = (ratio * direction + (ratio * cos_i - f64::sqrt(1. - sin2_t)) * normal)
+                .unit_vector();
+        }
+
+        Some((origin, direction))
+    }
+
+    // Axial positions of the focal point and principal plane of a ray that entered parallel to
+    // the axis at `height`.
+    fn cardinal_points(height: f64, ray: Option<(Point3, Vec3)>) -> Result<(f64, f64), String> {
+        let Some((origin, direction)) = ray else {
+            return Err("Rays close to the axis don't pass through the lens system".to_string());
+        };
+        if direction.x() == 0. {
+            return Err("The lens system does not converge light onto the film".to_string());
+        }
+
+        let focal_point = origin.z() - origin.x() / direction.x() * direction.z();
+        let principal_plane = origin.z() + (height - origin.x()) / direction.x() * direction.z();
+        Ok((focal_point, principal_plane))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Biconvex lens of 50 mm focal length with the aperture stop right behind it
+    fn single_lens() -> LensSystem {
+        LensSystem {
+            elements: vec![
+                LensElement {
+                    curvature_radius: 50.,
+                    thickness: 5.,
+                    refraction_index: 1.5,
+                    dispersion: None,
+                    aperture_diameter: 20.,
+                },
+                LensElement {
+                    curvature_radius: -50.,
+                    thickness: 1.,
+                    refraction_index: 1.,
+                    dispersion: None,
+                    aperture_diameter: 20.,
+                },
+                LensElement {
+                    curvature_radius: 0.,
+                    thickness: 0.,
+                    refraction_index: 1.,
+                    dispersion: None,
+                    aperture_diameter: 10.,
+                },
+            ],
+            film_diagonal_in_mm: full_frame_diagonal(),
+            world_units_per_mm: meters_per_millimeter(),
+        }
+    }
+
+    #[test]
+    fn rays_from_the_film_center_meet_at_the_focus_distance() {
+        let system = single_lens();
+        for focus_distance in [0.5, 2., 10.] {
+            let focus = system
+                .focus(focus_distance, 1.5, &Aperture::Circle)
+                .unwrap();
+            for lens_sample in [(0.02, 0.), (-0.02, 0.)] {
+                let (origin, direction) = system
+                    .ray(&focus, 0.5, 0.5, lens_sample, None, &Aperture::Circle)
+                    .unwrap();
+                // Where the ray crosses the axis, measured from the film
+                let z = origin.z() - origin.x() / direction.x() * direction.z();
+                assert!(
+                    (-z / focus_distance - 1.).abs() < 0.02,
+                    "focused at {} instead of {}",
+                    -z,
+                    focus_distance
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn focusing_closer_than_the_lens_can_fails() {
+        let system = single_lens();
+        // Four focal lengths are the closest a 50 mm lens forms an image at
+        assert!(system.focus(0.19, 1.5, &Aperture::Circle).is_err());
+        assert!(system.focus(0.21, 1.5, &Aperture::Circle).is_ok());
+    }
+
+    #[test]
+    fn lens_systems_without_an_image_fail_to_focus() {
+        let mut system = single_lens();
+        system.elements[0].curvature_radius = -50.;
+        system.elements[1].curvature_radius = 50.;
+        assert!(system.focus(1., 1.5, &Aperture::Circle).is_err());
+    }
+
+    #[test]
+    fn aperture_samples_lie_inside_the_opening() {
+        let apertures = [
+            Aperture::Circle,
+            Aperture::Polygon {
+                blades: 5,
+                rotation_in_degrees: 0.,
+            },
+            Aperture::Polygon {
+                blades: 6,
+                rotation_in_degrees: 20.,
+            },
+        ];
+        let steps = 64;
+        for aperture in &apertures {
+            let (mut sum_x, mut sum_y) = (0., 0.);
+            for step in 0..steps * steps {
+                let u = ((step % steps) as f64 + 0.5) / steps as f64;
+                let v = ((step / steps) as f64 + 0.5) / steps as f64;
+                let (x, y) = aperture.sample(u, v);
+                assert!(
+                    aperture.contains(x, y),
+                    "{:?} lies outside {:?}",
+                    (x, y),
+                    aperture
+                );
+                sum_x += x;
+                sum_y += y;
+            }
+            // Uniform over a symmetric opening
+            let count = (steps * steps) as f64;
+            assert!((sum_x / count).abs() < 0.01 && (sum_y / count).abs() < 0.01);
+        }
+    }
+
+    #[test]
+    fn polygons_cut_off_the_circle_between_their_corners() {
+        let hexagon = Aperture::Polygon {
+            blades: 6,
+            rotation_in_degrees: 0.,
+        };
+        let at = |angle: f64, radius: f64| {
+            let angle = degrees_to_radians(angle);
+            (radius * angle.cos(), radius * angle.sin())
+        };
+
+        // Corners at multiples of 60 degrees, edges halfway between them at cos 30 = 0.866
+        for corner in 0..6 {
+            let corner = 60. * corner as f64;
+            let (x, y) = at(corner, 0.99);
+            assert!(hexagon.contains(x, y));
+            let (x, y) = at(corner + 30., 0.85);
+            assert!(hexagon.contains(x, y));
+            let (x, y) = at(corner + 30., 0.88);
+            assert!(!hexagon.contains(x, y));
+        }
+        assert!(!Aperture::Circle.contains(0.8, 0.61));
+        assert!(Aperture::Circle.contains(0.8, 0.59));
+    }
+}
@@ -1,6 +1,20 @@
 pub mod animation;
 pub mod camera;
+pub mod checkpoint;
+pub mod film;
+pub mod filter;
 pub mod hittable;
+pub mod lens;
 pub mod material;
+pub mod medium;
+pub mod progress;
+pub mod projection;
 pub mod ray;
+pub mod sampler;
+pub mod shutter;
 pub mod scene;
+pub mod spectrum;
+pub mod stereo;
+pub mod texture;
+pub mod tile;
+pub mod video;
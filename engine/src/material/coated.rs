@@ -0,0 +1,124 @@
+use math::{onb::Onb, vec3::Color};
+use serde::{Deserialize, Serialize};
+
+use crate::{hittable::hittable::HitRecord, ray::Ray, sampler::sampler::Sampler};
+
+use super::{
+    fresnel::fresnel_dielectric,
+    interior::Interior,
+    material::{Material, ScatterKind},
+    object::MaterialObject,
+    thin_film::ThinFilm,
+    trowbridge_reitz::TrowbridgeReitz,
+};
+
+/// Clear dielectric layer over any other material, like the lacquer on car paint. The layer is
+/// assumed to be thin, light passing through it scatters off the base at the same point.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Coated {
+    pub base: Box<MaterialObject>,
+    #[serde(default = "coat_refraction_index")]
+    pub refraction_index: f64,
+    /// Perceptual roughness of the coat, 0 is a perfect mirror
+    #[serde(default)]
+    pub roughness: f64,
+    /// Tint of the light passing through the coat, applied on the way in and out
+    #[serde(default = "white")]
+    pub color: Color,
+    #[serde(default)]
+    pub thin_film: Option<ThinFilm>,
+}
+
+fn coat_refraction_index() -> f64 {
+    1.5
+}
+
+fn white() -> Color {
+    Color::one()
+}
+
+impl Coated {
+    fn reflectance(&self, cos_theta: f64, hit_record: &HitRecord, time: f64) -> Color {
+        match self.thin_film {
+            Some(thin_film) => thin_film.reflectance(
+                cos_theta,
+                hit_record.outside_refraction_index,
+                Color::one() * self.refraction_index,
+                Color::zero(),
+                time,
+            ),
+            None => {
+                Color::one()
+                    * fresnel_dielectric(
+                        cos_theta,
+                        self.refraction_index / hit_record.outside_refraction_index,
+                    )
+            }
+        }
+    }
+}
+
+impl Material for Coated {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        attenuation: &mut Color,
+        ray_scattered: &mut Ray,
+        sampler: &mut Sampler,
+    ) -> Option<ScatterKind> {
+        // The coat only exists on the outside of the object
+        if !hit_record.front_face {
+            return self
+                .base
+                .scatter(r_in, hit_record, attenuation, ray_scattered, sampler);
+        }
+
+        let onb = Onb::from_normal(hit_record.normal);
+        let wo = onb.to_local(r_in.direction().unit_vector().inverted());
+        if wo.z() <= 0. {
+            return None;
+        }
+
+        let distribution = TrowbridgeReitz::new(self.roughness, 0.);
+        let (u, v) = sampler.get_2d();
+        let wm = distribution.sample_visible_normal(wo, u, v);
+        let reflectance = self.reflectance(wo.dot(&wm), hit_record, r_in.time());
+
+        // Choose between the coat and the base proportional to the average reflectance of the
+        // coat, a tinted reflectance is left in the attenuation
+        let probability = (reflectance.x() + reflectance.y() + reflectance.z()) / 3.;
+        if sampler.get_1d() < probability {
+            let wi = wo.inverted().reflect(&wm);
+            if wi.z() <= 0. {
+                return None;
+            }
+            *attenuation =
+                reflectance / probability * distribution.g2(wo, wi) / distribution.g1(wo);
+            *ray_scattered = Ray::new_with_time(hit_record.p, onb.to_world(wi), r_in.time());
+            return Some(ScatterKind::Specular);
+        }
+
+        let mut base_attenuation = Color::zero();
+        let kind = self.base.scatter(
+            r_in,
+            hit_record,
+            &mut base_attenuation,
+            ray_scattered,
+            sampler,
+        )?;
+        *attenuation = base_attenuation * (Color::one() - reflectance) / (1. - probability)
+            * self.color
+            * self.color;
+
+        Some(kind)
+    }
+
+    fn is_dispersive(&self) -> bool {
+        self.base.is_dispersive()
+    }
+
+    fn interior(&self, hit_record: &HitRecord, wavelength: Option<f64>) -> Option<Interior> {
+        self.base.interior(hit_record, wavelength)
+    }
+}
@@ -1,15 +1,33 @@
-use math::{random::random_f64, vec3::Color};
+use math::vec3::Color;
 use serde::{Deserialize, Serialize};
 
-use crate::{hittable::hittable::HitRecord, ray::Ray};
+use crate::{hittable::hittable::HitRecord, ray::Ray, sampler::sampler::Sampler};
 
-use super::material::Material;
+use super::{
+    dispersion::{refraction_index_at, Dispersion},
+    interior::{absolute_refraction_index, Absorption, Interior},
+    material::{Material, ScatterKind},
+    thin_film::ThinFilm,
+};
 
 #[derive(Debug, Clone, Copy, Deserialize, Serialize)]
 pub struct Dialectric {
-    // Refractive index in vacuum or air, or the ratio of the material's refractive index over
-    // the refractive index of the enclosing media
+    // Refractive index relative to vacuum, the index of the dielectric the object is nested in
+    // is taken into account while tracing. Indices below 1 are relative to that dielectric.
     pub refraction_index: f64,
+    /// Replaces `refraction_index` in spectral mode
+    #[serde(default)]
+    pub dispersion: Option<Dispersion>,
+    #[serde(default)]
+    pub absorption: Option<Absorption>,
+    /// Overlapping dielectrics are only refracted through by the one with the highest priority,
+    /// e.g. give an air bubble a higher priority than the glass around it
+    #[serde(default)]
+    pub priority: u32,
+    /// Iridescent film on the surface, e.g. a soap bubble is a film of water on a dielectric
+    /// with a refraction index of 1
+    #[serde(default)]
+    pub thin_film: Option<ThinFilm>,
 }
 
 impl Dialectric {
@@ -27,13 +45,16 @@ impl Material for Dialectric {
         hit_record: &HitRecord,
         attenuation: &mut Color,
         ray_scattered: &mut Ray,
-    ) -> bool {
-        *attenuation = Color::new(1.0, 1.0, 1.0);
-
+        sampler: &mut Sampler,
+    ) -> Option<ScatterKind> {
+        let refraction_index = absolute_refraction_index(
+            refraction_index_at(self.refraction_index, self.dispersion, r_in.wavelength()),
+            hit_record.outside_refraction_index,
+        );
         let ri = if hit_record.front_face {
-            1.0 / self.refraction_index
+            hit_record.outside_refraction_index / refraction_index
         } else {
-            self.refraction_index
+            refraction_index / hit_record.outside_refraction_index
         };
 
         let unit_direction = r_in.direction().unit_vector();
@@ -42,15 +63,65 @@ impl Material for Dialectric {
 
         let cannot_refract = ri * sin_theta > 1.0;
 
-        let direction = if cannot_refract || Dialectric::reflactance(cos_theta, ri) > random_f64() {
-            unit_direction.reflect(&hit_record.normal)
-        } else {
-            unit_direction.refract(&hit_record.normal, ri)
+        let reflectance = match self.thin_film {
+            Some(thin_film) => {
+                let (outside, inside) = if hit_record.front_face {
+                    (hit_record.outside_refraction_index, refraction_index)
+                } else {
+                    (refraction_index, hit_record.outside_refraction_index)
+                };
+                thin_film.reflectance(
+                    cos_theta,
+                    outside,
+                    Color::one() * inside,
+                    Color::zero(),
+                    r_in.time(),
+                )
+            }
+            None => Color::one() * Dialectric::reflactance(cos_theta, ri),
         };
+        // Reflect with the average reflectance, a tinted reflectance is left in the attenuation
+        let probability = (reflectance.x() + reflectance.y() + reflectance.z()) / 3.;
 
-        // let refracted = unit_direction.refract(&hit_record.normal, ri);
+        let (direction, kind) = if cannot_refract {
+            *attenuation = Color::one();
+            (
+                unit_direction.reflect(&hit_record.normal),
+                ScatterKind::Specular,
+            )
+        } else if probability > sampler.get_1d() {
+            *attenuation = reflectance / probability;
+            (
+                unit_direction.reflect(&hit_record.normal),
+                ScatterKind::Specular,
+            )
+        } else {
+            *attenuation = (Color::one() - reflectance) / (1. - probability);
+            (
+                unit_direction.refract(&hit_record.normal, ri),
+                ScatterKind::Transmission,
+            )
+        };
 
         *ray_scattered = Ray::new_with_time(hit_record.p, direction, r_in.time());
-        return true;
+        Some(kind)
+    }
+
+    fn is_dispersive(&self) -> bool {
+        self.dispersion.is_some()
+    }
+
+    fn interior(&self, hit_record: &HitRecord, wavelength: Option<f64>) -> Option<Interior> {
+        Some(Interior {
+            object_id: hit_record.object_id,
+            priority: self.priority,
+            refraction_index: absolute_refraction_index(
+                refraction_index_at(self.refraction_index, self.dispersion, wavelength),
+                hit_record.outside_refraction_index,
+            ),
+            absorption: self
+                .absorption
+                .map_or(Color::zero(), |absorption| absorption.coefficients()),
+        })
     }
 }
@@ -0,0 +1,43 @@
+use serde::{Deserialize, Serialize};
+
+/// Wavelength dependent index of refraction of a dielectric, only used by the spectral
+/// integrator. Wavelengths in the formulas are in micrometers.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(tag = "type")]
+pub enum Dispersion {
+    /// n(λ) = a + b / λ²
+    Cauchy { a: f64, b: f64 },
+    /// n²(λ) = 1 + Σ bᵢ λ² / (λ² - cᵢ)
+    Sellmeier { b: [f64; 3], c: [f64; 3] },
+}
+
+/// Index of refraction of a dielectric at the wavelength of a ray, `dispersion` only applies in
+/// spectral mode.
+pub fn refraction_index_at(
+    refraction_index: f64,
+    dispersion: Option<Dispersion>,
+    wavelength: Option<f64>,
+) -> f64 {
+    match (dispersion, wavelength) {
+        (Some(dispersion), Some(wavelength)) => dispersion.refraction_index(wavelength),
+        _ => refraction_index,
+    }
+}
+
+impl Dispersion {
+    pub fn refraction_index(&self, wavelength_in_nm: f64) -> f64 {
+        let lambda = wavelength_in_nm / 1000.;
+        let lambda2 = lambda * lambda;
+
+        match *self {
+            Dispersion::Cauchy { a, b } => a + b / lambda2,
+            Dispersion::Sellmeier { b, c } => f64::sqrt(
+                1. + b
+                    .iter()
+                    .zip(c)
+                    .map(|(b, c)| b * lambda2 / (lambda2 - c))
+                    .sum::<f64>(),
+            ),
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use math::vec3::Color;
+use serde::{Deserialize, Serialize};
+
+/// Fraction of light reflected at an interface depending on the angle of incidence.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(tag = "type")]
+pub enum Fresnel {
+    /// Metals, described by the real (`eta`) and imaginary (`k`) part of their complex index of
+    /// refraction per color channel
+    Conductor { eta: Color, k: Color },
+    /// Non conducting coatings, light that isn't reflected is absorbed
+    Dielectric { refraction_index: f64 },
+}
+
+impl Fresnel {
+    pub fn evaluate(&self, cos_theta_i: f64) -> Color {
+        match *self {
+            Fresnel::Conductor { eta, k } => Color::new(
+                fresnel_conductor(cos_theta_i, eta.x(), k.x()),
+                fresnel_conductor(cos_theta_i, eta.y(), k.y()),
+                fresnel_conductor(cos_theta_i, eta.z(), k.z()),
+            ),
+            Fresnel::Dielectric { refraction_index } => {
+                Color::one() * fresnel_dielectric(cos_theta_i, refraction_index)
+            }
+        }
+    }
+}
+
+/// Unpolarized reflectance of a dielectric interface, `eta` is the index of refraction of the
+/// far side over the one of the side the light comes from. Negative cosines are treated as light
+/// arriving from the far side.
+pub fn fresnel_dielectric(cos_theta_i: f64, eta: f64) -> f64 {
+    let (cos_theta_i, eta) = if cos_theta_i < 0. {
+        (-cos_theta_i, 1. / eta)
+    } else {
+        (cos_theta_i, eta)
+    };
+    let cos_theta_i = f64::min(cos_theta_i, 1.);
+
+    let sin2_theta_t = (1. - cos_theta_i * cos_theta_i) / (eta * eta);
+    if sin2_theta_t >= 1. {
+        // Total internal reflection
+        return 1.;
+    }
+    let cos_theta_t = f64::sqrt(1. - sin2_theta_t);
+
+    let r_parallel = (eta * cos_theta_i - cos_theta_t) / (eta * cos_theta_i + cos_theta_t);
+    let r_perpendicular = (cos_theta_i - eta * cos_theta_t) / (cos_theta_i + eta * cos_theta_t);
+    (r_parallel * r_parallel + r_perpendicular * r_perpendicular) / 2.
+}
+
+/// Unpolarized reflectance of a conductor with complex index of refraction `eta + i k`.
+pub fn fresnel_conductor(cos_theta_i: f64, eta: f64, k: f64) -> f64 {
+    let cos_theta_i = cos_theta_i.clamp(0., 1.);
+    let cos2 = cos_theta_i * cos_theta_i;
+    let sin2 = 1. - cos2;
+
+    let t0 = eta * eta - k * k - sin2;
+    let a2_plus_b2 = f64::sqrt(t0 * t0 + 4. * eta * eta * k * k);
+    let a = f64::sqrt(f64::max(0.5 * (a2_plus_b2 + t0), 0.));
+
+    let t1 = a2_plus_b2 + cos2;
+    let t2 = 2. * a * cos_theta_i;
+    let r_s = (t1 - t2) / (t1 + t2);
+
+    let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+    let t4 = t2 * sin2;
+    let r_p = r_s * (t3 - t4) / (t3 + t4);
+
+    (r_s + r_p) / 2.
+}
+
+/// Schlick's approximation of the reflectance for a normal incidence reflectance of `f0`.
+pub fn fresnel_schlick(f0: Color, cos_theta_i: f64) -> Color {
+    f0 + (Color::one() - f0) * schlick_weight(cos_theta_i)
+}
+
+pub fn schlick_weight(cos_theta: f64) -> f64 {
+    (1. - cos_theta.clamp(0., 1.)).powi(5)
+}
+
+/// Complex index of refraction `(eta, k)` of a conductor reflecting `reflectivity` at normal
+/// incidence, tinted towards `edge_tint` at grazing angles (Gulbrandsen, "Artist Friendly
+/// Metallic Fresnel").
+pub fn conductor_from_reflectivity(reflectivity: f64, edge_tint: f64) -> (f64, f64) {
+    let r = reflectivity.clamp(0., 0.999);
+    let g = edge_tint.clamp(0., 1.);
+
+    let n_min = (1. - r) / (1. + r);
+    let n_max = (1. + r.sqrt()) / (1. - r.sqrt());
+    let eta = g * n_min + (1. - g) * n_max;
+
+    let k2 = ((eta + 1.) * (eta + 1.) * r - (eta - 1.) * (eta - 1.)) / (1. - r);
+    (eta, f64::max(k2, 0.).sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dielectric_limits() {
+        // Glass reflects 4% head on and everything at grazing angles
+        assert!((fresnel_dielectric(1., 1.5) - 0.04).abs() < 1e-12);
+        assert!((fresnel_dielectric(1e-9, 1.5) - 1.).abs() < 1e-6);
+        assert_eq!(fresnel_dielectric(0.7, 1.), 0.);
+    }
+
+    #[test]
+    fn dielectric_reflects_totally_beyond_the_critical_angle() {
+        // From inside glass the critical angle is at asin(1 / 1.5), about 41.8 degrees
+        let critical_cos = f64::cos(f64::asin(1. / 1.5));
+        assert_eq!(fresnel_dielectric(-(critical_cos - 0.01), 1.5), 1.);
+        assert!(fresnel_dielectric(-(critical_cos + 0.01), 1.5) < 1.);
+        assert!((fresnel_dielectric(-1., 1.5) - 0.04).abs() < 1e-12);
+    }
+
+    #[test]
+    fn conductor_without_absorption_is_a_dielectric() {
+        for cos_theta in [0.1, 0.5, 0.9, 1.] {
+            let conductor = fresnel_conductor(cos_theta, 1.5, 0.);
+            assert!((conductor - fresnel_dielectric(cos_theta, 1.5)).abs() < 1e-12);
+        }
+        assert!((fresnel_conductor(0., 0.2, 3.) - 1.).abs() < 1e-12);
+    }
+
+    #[test]
+    fn conductor_from_reflectivity_reflects_it_head_on() {
+        for reflectivity in [0.05, 0.5, 0.95] {
+            for edge_tint in [0., 0.5, 1.] {
+                let (eta, k) = conductor_from_reflectivity(reflectivity, edge_tint);
+                let reflected = fresnel_conductor(1., eta, k);
+                assert!(
+                    (reflected - reflectivity).abs() < 1e-9,
+                    "{} {}",
+                    reflectivity,
+                    edge_tint
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn schlick_limits() {
+        let f0 = Color::new(0.1, 0.5, 0.9);
+        assert_eq!(fresnel_schlick(f0, 1.), f0);
+        let grazing = fresnel_schlick(f0, 0.);
+        assert!((grazing - Color::one()).length() < 1e-12);
+    }
+}
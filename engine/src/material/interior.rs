@@ -0,0 +1,205 @@
+use math::vec3::Color;
+use serde::{Deserialize, Serialize};
+
+/// Color light is tinted with inside a dielectric (Beer-Lambert law).
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub struct Absorption {
+    /// Transmittance after light travelled `distance` through the material
+    pub color: Color,
+    pub distance: f64,
+}
+
+impl Absorption {
+    /// Absorption coefficients per unit of distance.
+    pub fn coefficients(&self) -> Color {
+        let coefficient = |transmittance: f64| {
+            -f64::ln(transmittance.clamp(1e-6, 1.)) / f64::max(self.distance, 1e-6)
+        };
+        Color::new(
+            coefficient(self.color.x()),
+            coefficient(self.color.y()),
+            coefficient(self.color.z()),
+        )
+    }
+}
+
+/// Index of refraction relative to vacuum of a dielectric surrounded by
+/// `outside_refraction_index`. Before dielectrics were nested, an object inside of another one
+/// had an index relative to the one around it, e.g. 1/1.5 for an air bubble in glass. Indices
+/// below 1 are still read that way, so these scenes render as before.
+pub fn absolute_refraction_index(refraction_index: f64, outside_refraction_index: f64) -> f64 {
+    if refraction_index < 1. {
+        refraction_index * outside_refraction_index
+    } else {
+        refraction_index
+    }
+}
+
+/// A dielectric a path can be inside of.
+#[derive(Debug, Clone, Copy)]
+pub struct Interior {
+    /// The object the path entered the dielectric through, see `HitRecord::object_id`
+    pub object_id: usize,
+    /// Where dielectrics overlap the path is inside of the one with the highest priority
+    pub priority: u32,
+    pub refraction_index: f64,
+    /// Absorption coefficients per unit of distance
+    pub absorption: Color,
+}
+
+/// Dielectrics a path is currently inside of, in the order it entered them (Schmidt and Budge,
+/// "Simple Nested Dielectrics in Ray Traced Images"). Surfaces of dielectrics inside of one with
+/// a higher priority are false intersections the path passes straight through, which lets
+/// overlapping objects such as liquid in a glass share their boundaries.
+#[derive(Debug, Clone, Default)]
+pub struct InteriorList {
+    entries: Vec<Interior>,
+}
+
+impl InteriorList {
+    /// The dielectric the path travels through, the last entered one of the highest priority.
+    pub fn current(&self) -> Option<&Interior> {
+        self.entries.iter().max_by_key(|entry| entry.priority)
+    }
+
+    /// Index of refraction around the path, 1 outside of all dielectrics.
+    pub fn refraction_index(&self) -> f64 {
+        self.current().map_or(1., |entry| entry.refraction_index)
+    }
+
+    /// Index of refraction around the path once it left the object `object_id`.
+    pub fn refraction_index_outside_of(&self, object_id: usize) -> f64 {
+        let left = self
+            .entries
+            .iter()
+            .rposition(|entry| entry.object_id == object_id);
+        self.entries
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| Some(*index) != left)
+            .map(|(_, entry)| entry)
+            .max_by_key(|entry| entry.priority)
+            .map_or(1., |entry| entry.refraction_index)
+    }
+
+    /// Fraction of the light that makes it through `distance` of the current dielectric.
+    pub fn transmittance(&self, distance: f64) -> Color {
+        let Some(current) = self.current() else {
+            return Color::one();
+        };
+        let absorption = current.absorption;
+        Color::new(
+            f64::exp(-absorption.x() * distance),
+            f64::exp(-absorption.y() * distance),
+            f64::exp(-absorption.z() * distance),
+        )
+    }
+
+    pub fn is_false_intersection(&self, interior: &Interior) -> bool {
+        self.entries
+            .iter()
+            .any(|entry| entry.priority > interior.priority)
+    }
+
+    pub fn enter(&mut self, interior: Interior) {
+        self.entries.push(interior);
+    }
+
+    pub fn leave(&mut self, interior: &Interior) {
+        if let Some(index) = self
+            .entries
+            .iter()
+            .rposition(|entry| entry.object_id == interior.object_id)
+        {
+            self.entries.remove(index);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::dialectric::Dialectric;
+
+    fn glass(object_id: usize, priority: u32, refraction_index: f64) -> Interior {
+        Interior {
+            object_id,
+            priority,
+            refraction_index,
+            absorption: Color::zero(),
+        }
+    }
+
+    #[test]
+    fn leaving_restores_the_dielectric_around() {
+        let mut list = InteriorList::default();
+        assert_eq!(list.refraction_index(), 1.);
+
+        list.enter(glass(1, 0, 1.5));
+        list.enter(glass(2, 0, 1.33));
+        assert_eq!(list.refraction_index(), 1.33);
+        assert_eq!(list.refraction_index_outside_of(2), 1.5);
+
+        list.leave(&glass(2, 0, 1.33));
+        assert_eq!(list.refraction_index(), 1.5);
+        list.leave(&glass(1, 0, 1.5));
+        assert_eq!(list.refraction_index(), 1.);
+    }
+
+    #[test]
+    fn objects_are_left_in_any_order() {
+        // Overlapping objects are left through the one entered first
+        let mut list = InteriorList::default();
+        list.enter(glass(1, 0, 1.5));
+        list.enter(glass(2, 0, 1.33));
+        list.leave(&glass(1, 0, 1.5));
+        assert_eq!(list.refraction_index(), 1.33);
+        assert_eq!(list.refraction_index_outside_of(2), 1.);
+
+        // Leaving an object that wasn't entered changes nothing
+        list.leave(&glass(3, 0, 2.));
+        assert_eq!(list.refraction_index(), 1.33);
+    }
+
+    #[test]
+    fn higher_priorities_hide_the_surfaces_of_lower_ones() {
+        let mut list = InteriorList::default();
+        let bubble = glass(1, 1, 1.);
+        let liquid = glass(2, 0, 1.33);
+
+        list.enter(bubble);
+        assert!(list.is_false_intersection(&liquid));
+        assert!(!list.is_false_intersection(&bubble));
+
+        // The path travels through the bubble even after entering the liquid around it
+        list.enter(liquid);
+        assert_eq!(list.refraction_index(), 1.);
+        assert_eq!(list.refraction_index_outside_of(1), 1.33);
+    }
+
+    #[test]
+    fn absorption_follows_beer_lambert() {
+        let mut list = InteriorList::default();
+        assert_eq!(list.transmittance(10.), Color::one());
+
+        let absorption = Absorption {
+            color: Color::new(0.5, 0.25, 1.),
+            distance: 2.,
+        };
+        list.enter(Interior {
+            absorption: absorption.coefficients(),
+            ..glass(1, 0, 1.5)
+        });
+        let transmittance = list.transmittance(4.);
+        assert!((transmittance - Color::new(0.25, 0.0625, 1.)).length() < 1e-12);
+    }
+
+    #[test]
+    fn relative_indices_keep_working() {
+        // An air bubble in glass written the way it was before dielectrics were nested
+        let bubble: Dialectric = toml::from_str("refraction_index = 0.6666666666666666").unwrap();
+        let absolute = absolute_refraction_index(bubble.refraction_index, 1.5);
+        assert!((absolute - 1.).abs() < 1e-12);
+        assert_eq!(absolute_refraction_index(1.5, 1.33), 1.5);
+    }
+}
@@ -1,9 +1,9 @@
 use math::vec3::{Color, Vec3};
 use serde::{Deserialize, Serialize};
 
-use crate::{hittable::hittable::HitRecord, ray::Ray};
+use crate::{hittable::hittable::HitRecord, ray::Ray, sampler::sampler::Sampler};
 
-use super::material::Material;
+use super::material::{Material, ScatterKind};
 
 #[derive(Debug, Clone, Copy, Deserialize, Serialize)]
 pub struct Lambertian {
@@ -17,8 +17,10 @@ impl Material for Lambertian {
         hit_record: &HitRecord,
         attenuation: &mut Color,
         ray_scattered: &mut Ray,
-    ) -> bool {
-        let mut scatter_direction = hit_record.normal + Vec3::random_unit_vector();
+        sampler: &mut Sampler,
+    ) -> Option<ScatterKind> {
+        let (u, v) = sampler.get_2d();
+        let mut scatter_direction = hit_record.normal + Vec3::unit_vector_from_sample(u, v);
 
         // Catch degenerate scatter direction
         if scatter_direction.is_near_zero() {
@@ -28,6 +30,6 @@ impl Material for Lambertian {
         *ray_scattered = Ray::new_with_time(hit_record.p, scatter_direction, r_in.time());
         *attenuation = self.albedo;
 
-        true
+        Some(ScatterKind::Diffuse)
     }
 }
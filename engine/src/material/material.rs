@@ -1,13 +1,40 @@
 use math::vec3::Color;
 
-use crate::{hittable::hittable::HitRecord, ray::Ray};
+use crate::{hittable::hittable::HitRecord, ray::Ray, sampler::sampler::Sampler};
+
+use super::interior::Interior;
+
+/// What happened to a ray at a surface or inside a medium, the integrator keeps separate bounce
+/// limits for each.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScatterKind {
+    Diffuse,
+    Specular,
+    Transmission,
+    /// Scattered by a participating medium, never returned by materials
+    Volume,
+}
 
 pub trait Material {
+    /// Returns `None` if the ray was absorbed.
     fn scatter(
         &self,
         r_in: &Ray,
         hit_record: &HitRecord,
         attenuation: &mut Color,
         ray_scattered: &mut Ray,
-    ) -> bool;
+        sampler: &mut Sampler,
+    ) -> Option<ScatterKind>;
+
+    /// Whether the scattered direction depends on the wavelength of the ray, the spectral
+    /// integrator then only keeps the hero wavelength of the path.
+    fn is_dispersive(&self) -> bool {
+        false
+    }
+
+    /// The dielectric a path is inside of after refracting through the surface at `hit_record`,
+    /// evaluated at the wavelength of the path in spectral mode.
+    fn interior(&self, _hit_record: &HitRecord, _wavelength: Option<f64>) -> Option<Interior> {
+        None
+    }
 }
@@ -1,14 +1,20 @@
 use math::vec3::{Color, Vec3};
 use serde::{Deserialize, Serialize};
 
-use crate::{hittable::hittable::HitRecord, ray::Ray};
+use crate::{hittable::hittable::HitRecord, ray::Ray, sampler::sampler::Sampler};
 
-use super::material::Material;
+use super::{
+    fresnel::conductor_from_reflectivity,
+    material::{Material, ScatterKind},
+    thin_film::ThinFilm,
+};
 
 #[derive(Debug, Clone, Copy, Deserialize, Serialize)]
 pub struct Metal {
     pub albedo: Color,
     pub fuzz: f64,
+    #[serde(default)]
+    pub thin_film: Option<ThinFilm>,
 }
 
 impl Material for Metal {
@@ -18,11 +24,30 @@ impl Material for Metal {
         hit_record: &HitRecord,
         attenuation: &mut Color,
         ray_scattered: &mut Ray,
-    ) -> bool {
+        sampler: &mut Sampler,
+    ) -> Option<ScatterKind> {
         let mut reflected = r_in.direction().reflect(&hit_record.normal);
-        reflected = reflected.unit_vector() + (self.fuzz * Vec3::random_unit_vector());
+        let (u, v) = sampler.get_2d();
+        reflected = reflected.unit_vector() + (self.fuzz * Vec3::unit_vector_from_sample(u, v));
         *ray_scattered = Ray::new_with_time(hit_record.p, reflected, r_in.time());
-        *attenuation = self.albedo;
-        return ray_scattered.direction().dot(&hit_record.normal) > 0.;
+        *attenuation = match self.thin_film {
+            Some(thin_film) => {
+                // The metal underneath is derived from the albedo
+                let [r, g, b] = self
+                    .albedo
+                    .to_array()
+                    .map(|reflectivity| conductor_from_reflectivity(reflectivity, reflectivity));
+                let cos_theta = -r_in.direction().unit_vector().dot(&hit_record.normal);
+                thin_film.reflectance(
+                    cos_theta,
+                    hit_record.outside_refraction_index,
+                    Color::new(r.0, g.0, b.0),
+                    Color::new(r.1, g.1, b.1),
+                    r_in.time(),
+                )
+            }
+            None => self.albedo,
+        };
+        (ray_scattered.direction().dot(&hit_record.normal) > 0.).then_some(ScatterKind::Specular)
     }
 }
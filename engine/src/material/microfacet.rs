@@ -0,0 +1,75 @@
+use math::{onb::Onb, vec3::Color};
+use serde::{Deserialize, Serialize};
+
+use crate::{hittable::hittable::HitRecord, ray::Ray, sampler::sampler::Sampler};
+
+use super::{
+    fresnel::Fresnel,
+    material::{Material, ScatterKind},
+    thin_film::ThinFilm,
+    trowbridge_reitz::TrowbridgeReitz,
+};
+
+/// Glossy reflection off a rough surface described by a GGX microfacet distribution. Unlike
+/// `Metal`'s fuzz this conserves energy apart from what the fresnel term absorbs.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub struct Microfacet {
+    /// Perceptual roughness, 0 is a perfect mirror
+    pub roughness: f64,
+    #[serde(default)]
+    pub anisotropy: f64,
+    pub fresnel: Fresnel,
+    #[serde(default)]
+    pub thin_film: Option<ThinFilm>,
+}
+
+impl Material for Microfacet {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        attenuation: &mut Color,
+        ray_scattered: &mut Ray,
+        sampler: &mut Sampler,
+    ) -> Option<ScatterKind> {
+        let onb = Onb::from_normal(hit_record.normal);
+        let wo = onb.to_local(r_in.direction().unit_vector().inverted());
+        if wo.z() <= 0. {
+            return None;
+        }
+
+        let distribution = TrowbridgeReitz::new(self.roughness, self.anisotropy);
+        let (u, v) = sampler.get_2d();
+        let wm = distribution.sample_visible_normal(wo, u, v);
+
+        let wi = wo.inverted().reflect(&wm);
+        if wi.z() <= 0. {
+            return None;
+        }
+
+        // Sampling visible normals leaves only the fresnel and the masking of the outgoing
+        // direction in the sample weight
+        let fresnel = match self.thin_film {
+            Some(thin_film) => {
+                let (eta, k) = match self.fresnel {
+                    Fresnel::Conductor { eta, k } => (eta, k),
+                    Fresnel::Dielectric { refraction_index } => {
+                        (Color::one() * refraction_index, Color::zero())
+                    }
+                };
+                thin_film.reflectance(
+                    wo.dot(&wm),
+                    hit_record.outside_refraction_index,
+                    eta,
+                    k,
+                    r_in.time(),
+                )
+            }
+            None => self.fresnel.evaluate(wo.dot(&wm)),
+        };
+        *attenuation = fresnel * distribution.g2(wo, wi) / distribution.g1(wo);
+        *ray_scattered = Ray::new_with_time(hit_record.p, onb.to_world(wi), r_in.time());
+
+        Some(ScatterKind::Specular)
+    }
+}
@@ -1,5 +1,16 @@
-pub mod lambertian;
-pub mod metal;
+pub mod coated;
 pub mod dialectric;
+pub mod dispersion;
+pub mod fresnel;
+pub mod interior;
+pub mod lambertian;
+#[allow(clippy::module_inception)]
 pub mod material;
-pub mod object;
\ No newline at end of file
+pub mod metal;
+pub mod microfacet;
+pub mod normal_map;
+pub mod object;
+pub mod principled;
+pub mod rough_dielectric;
+pub mod thin_film;
+pub mod trowbridge_reitz;
@@ -0,0 +1,186 @@
+use math::vec3::{Color, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    hittable::hittable::HitRecord,
+    ray::Ray,
+    sampler::sampler::Sampler,
+    texture::{ImageTexture, Texture},
+};
+
+use super::{
+    interior::Interior,
+    material::{Material, ScatterKind},
+    object::MaterialObject,
+};
+
+// Step in surface coordinates the slope of a bump map is measured over.
+const BUMP_DELTA: f64 = 0.0005;
+
+/// Adds surface detail to another material by perturbing the normal it scatters with, the
+/// geometry itself stays unchanged.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct NormalMapped {
+    pub base: Box<MaterialObject>,
+    pub map: NormalMap,
+    /// Scales the perturbation, 0 leaves the normal unchanged
+    #[serde(default = "one")]
+    pub strength: f64,
+}
+
+fn one() -> f64 {
+    1.
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type")]
+pub enum NormalMap {
+    /// Tangent space normal map, red along the tangent, green along the bitangent and blue away
+    /// from the surface
+    Normal { image: ImageTexture },
+    /// Height field displacing the surface along the normal, in world units
+    Bump { height: Texture<f64> },
+}
+
+impl NormalMapped {
+    fn shading_normal(&self, hit_record: &HitRecord) -> Vec3 {
+        let normal = hit_record.normal;
+        let perturbed = match &self.map {
+            NormalMap::Normal { image } => {
+                let texel = image.sample(hit_record.u, hit_record.v);
+                let local = 2. * texel - Color::one();
+
+                let tangent =
+                    (hit_record.dpdu - normal * normal.dot(&hit_record.dpdu)).unit_vector();
+                let bitangent = (hit_record.dpdv
+                    - normal * normal.dot(&hit_record.dpdv)
+                    - tangent * tangent.dot(&hit_record.dpdv))
+                .unit_vector();
+
+                self.strength * (local.x() * tangent + local.y() * bitangent) + local.z() * normal
+            }
+            NormalMap::Bump { height } => {
+                let displacement = |du: f64, dv: f64| {
+                    height.value_at(
+                        hit_record.p + du * hit_record.dpdu + dv * hit_record.dpdv,
+                        hit_record.u + du,
+                        hit_record.v + dv,
+                    )
+                };
+                let center = displacement(0., 0.);
+                let dhdu = (displacement(BUMP_DELTA, 0.) - center) / BUMP_DELTA;
+                let dhdv = (displacement(0., BUMP_DELTA) - center) / BUMP_DELTA;
+
+                // Keep it on the side of the hit normal, whichever way the coordinates wind
+                let winding = hit_record
+                    .dpdu
+                    .cross(&hit_record.dpdv)
+                    .dot(&normal)
+                    .signum();
+                let dpdu = hit_record.dpdu + self.strength * dhdu * normal;
+                let dpdv = hit_record.dpdv + self.strength * dhdv * normal;
+                winding * dpdu.cross(&dpdv)
+            }
+        };
+
+        if perturbed.is_near_zero() || perturbed.dot(&normal) <= 0. {
+            return normal;
+        }
+        perturbed.unit_vector()
+    }
+}
+
+impl Material for NormalMapped {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        attenuation: &mut Color,
+        ray_scattered: &mut Ray,
+        sampler: &mut Sampler,
+    ) -> Option<ScatterKind> {
+        // A normal facing away from the incoming ray would scatter it from behind the surface
+        let normal = Some(self.shading_normal(hit_record))
+            .filter(|normal| r_in.direction().dot(normal) < 0.)
+            .unwrap_or(hit_record.normal);
+        let shading = HitRecord {
+            normal,
+            ..*hit_record
+        };
+
+        self.base
+            .scatter(r_in, &shading, attenuation, ray_scattered, sampler)
+    }
+
+    fn is_dispersive(&self) -> bool {
+        self.base.is_dispersive()
+    }
+
+    fn interior(&self, hit_record: &HitRecord, wavelength: Option<f64>) -> Option<Interior> {
+        self.base.interior(hit_record, wavelength)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use math::vec3::Point3;
+
+    use super::*;
+    use crate::material::lambertian::Lambertian;
+
+    fn bumped(height: Texture<f64>, strength: f64) -> NormalMapped {
+        NormalMapped {
+            base: Box::new(MaterialObject::Lambertian(Lambertian {
+                albedo: Color::one(),
+            })),
+            map: NormalMap::Bump { height },
+            strength,
+        }
+    }
+
+    fn hit_record() -> HitRecord<'static> {
+        HitRecord {
+            p: Point3::new(0.1, 0., 0.1),
+            normal: Vec3::new(0., 1., 0.),
+            front_face: true,
+            u: 0.3,
+            v: 0.6,
+            dpdu: Vec3::new(1., 0., 0.),
+            dpdv: Vec3::new(0., 0., -1.),
+            ..HitRecord::default()
+        }
+    }
+
+    #[test]
+    fn flat_bumps_keep_the_normal() {
+        let normal = bumped(Texture::Constant(0.2), 1.).shading_normal(&hit_record());
+        assert!((normal - Vec3::new(0., 1., 0.)).length() < 1e-12);
+    }
+
+    #[test]
+    fn bumps_tilt_the_normal_down_the_slope() {
+        // A checker step right next to the hit point in the direction of u
+        let height = Texture::Checker {
+            even: 0.,
+            odd: 1e-4,
+            scale: 0.10025,
+        };
+        let normal = bumped(height.clone(), 1.).shading_normal(&hit_record());
+        assert!(normal.x() < -0.1, "{:?}", normal);
+        assert!(normal.y() > 0. && (normal.length() - 1.).abs() < 1e-12);
+
+        let normal = bumped(height, 0.).shading_normal(&hit_record());
+        assert!((normal - Vec3::new(0., 1., 0.)).length() < 1e-12);
+    }
+
+    #[test]
+    fn base_material_is_read_inline() {
+        let material: NormalMapped = toml::from_str(
+            "base = { type = \"Lambertian\", albedo = [0.5, 0.5, 0.5] }\n\
+             map = { type = \"Bump\", height = 0.1 }",
+        )
+        .unwrap();
+        assert_eq!(material.strength, 1.);
+        assert!(matches!(*material.base, MaterialObject::Lambertian(_)));
+    }
+}
@@ -1,16 +1,32 @@
 use math::vec3::Color;
 use serde::{Deserialize, Serialize};
 
-use crate::{hittable::hittable::HitRecord, ray::Ray};
+use crate::{hittable::hittable::HitRecord, ray::Ray, sampler::sampler::Sampler};
 
-use super::{dialectric::Dialectric, lambertian::Lambertian, material::Material, metal::Metal};
+use super::{
+    coated::Coated,
+    dialectric::Dialectric,
+    interior::Interior,
+    lambertian::Lambertian,
+    material::{Material, ScatterKind},
+    metal::Metal,
+    microfacet::Microfacet,
+    normal_map::NormalMapped,
+    principled::Principled,
+    rough_dielectric::RoughDielectric,
+};
 
-#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(tag = "type")]
 pub enum MaterialObject {
     Lambertian(Lambertian),
     Metal(Metal),
     Dielectric(Dialectric),
+    Microfacet(Microfacet),
+    RoughDielectric(RoughDielectric),
+    Principled(Box<Principled>),
+    Coated(Coated),
+    NormalMapped(NormalMapped),
 }
 
 impl Material for MaterialObject {
@@ -20,17 +36,58 @@ impl Material for MaterialObject {
         hit_record: &HitRecord,
         attenuation: &mut Color,
         ray_scattered: &mut Ray,
-    ) -> bool {
+        sampler: &mut Sampler,
+    ) -> Option<ScatterKind> {
         match self {
             MaterialObject::Lambertian(lambertian) => {
-                lambertian.scatter(r_in, hit_record, attenuation, ray_scattered)
+                lambertian.scatter(r_in, hit_record, attenuation, ray_scattered, sampler)
             }
             MaterialObject::Metal(metal) => {
-                metal.scatter(r_in, hit_record, attenuation, ray_scattered)
+                metal.scatter(r_in, hit_record, attenuation, ray_scattered, sampler)
             }
             MaterialObject::Dielectric(dielectric) => {
-                dielectric.scatter(r_in, hit_record, attenuation, ray_scattered)
+                dielectric.scatter(r_in, hit_record, attenuation, ray_scattered, sampler)
             }
+            MaterialObject::Microfacet(microfacet) => {
+                microfacet.scatter(r_in, hit_record, attenuation, ray_scattered, sampler)
+            }
+            MaterialObject::RoughDielectric(rough_dielectric) => {
+                rough_dielectric.scatter(r_in, hit_record, attenuation, ray_scattered, sampler)
+            }
+            MaterialObject::Principled(principled) => {
+                principled.scatter(r_in, hit_record, attenuation, ray_scattered, sampler)
+            }
+            MaterialObject::Coated(coated) => {
+                coated.scatter(r_in, hit_record, attenuation, ray_scattered, sampler)
+            }
+            MaterialObject::NormalMapped(normal_mapped) => {
+                normal_mapped.scatter(r_in, hit_record, attenuation, ray_scattered, sampler)
+            }
+        }
+    }
+
+    fn is_dispersive(&self) -> bool {
+        match self {
+            MaterialObject::Dielectric(dielectric) => dielectric.is_dispersive(),
+            MaterialObject::RoughDielectric(rough_dielectric) => rough_dielectric.is_dispersive(),
+            MaterialObject::Coated(coated) => coated.is_dispersive(),
+            MaterialObject::NormalMapped(normal_mapped) => normal_mapped.is_dispersive(),
+            _ => false,
+        }
+    }
+
+    fn interior(&self, hit_record: &HitRecord, wavelength: Option<f64>) -> Option<Interior> {
+        match self {
+            MaterialObject::Dielectric(dielectric) => dielectric.interior(hit_record, wavelength),
+            MaterialObject::RoughDielectric(rough_dielectric) => {
+                rough_dielectric.interior(hit_record, wavelength)
+            }
+            MaterialObject::Principled(principled) => principled.interior(hit_record, wavelength),
+            MaterialObject::Coated(coated) => coated.interior(hit_record, wavelength),
+            MaterialObject::NormalMapped(normal_mapped) => {
+                normal_mapped.interior(hit_record, wavelength)
+            }
+            _ => None,
         }
     }
 }
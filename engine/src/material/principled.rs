@@ -0,0 +1,280 @@
+use math::{
+    onb::Onb,
+    vec3::{Color, Vec3},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{hittable::hittable::HitRecord, ray::Ray, sampler::sampler::Sampler, texture::Texture};
+
+use super::{
+    fresnel::{fresnel_dielectric, fresnel_schlick, schlick_weight},
+    interior::Interior,
+    material::{Material, ScatterKind},
+    rough_dielectric::refract,
+    trowbridge_reitz::TrowbridgeReitz,
+};
+
+/// Disney style principled material combining a diffuse base with sheen, a GGX specular layer,
+/// rough transmission and a clear coat, blended by a handful of artist friendly parameters.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Principled {
+    pub base_color: Texture<Color>,
+    /// 0 is a dielectric, 1 a metal tinted by the base color
+    #[serde(default = "zero")]
+    pub metallic: Texture<f64>,
+    #[serde(default = "half")]
+    pub roughness: Texture<f64>,
+    /// Strength of the dielectric reflection, 0.5 matches the index of refraction
+    #[serde(default = "half")]
+    pub specular: Texture<f64>,
+    /// Soft retro reflection at grazing angles, for cloth
+    #[serde(default = "zero")]
+    pub sheen: Texture<f64>,
+    /// How much the sheen is tinted by the base color
+    #[serde(default = "half")]
+    pub sheen_tint: Texture<f64>,
+    /// Strength of a thin, glossy, uncolored coat on top
+    #[serde(default = "zero")]
+    pub clearcoat: Texture<f64>,
+    #[serde(default = "clearcoat_roughness")]
+    pub clearcoat_roughness: Texture<f64>,
+    /// Fraction of the dielectric base that transmits instead of scattering diffusely
+    #[serde(default = "zero")]
+    pub transmission: Texture<f64>,
+    #[serde(default = "ior")]
+    pub ior: Texture<f64>,
+    /// Priority of the transmissive interior among overlapping dielectrics, see `Dialectric`
+    #[serde(default)]
+    pub priority: u32,
+}
+
+fn zero() -> Texture<f64> {
+    Texture::Constant(0.)
+}
+
+fn half() -> Texture<f64> {
+    Texture::Constant(0.5)
+}
+
+fn clearcoat_roughness() -> Texture<f64> {
+    Texture::Constant(0.1)
+}
+
+fn ior() -> Texture<f64> {
+    Texture::Constant(1.5)
+}
+
+// The parameters evaluated at a hit point.
+struct Parameters {
+    base_color: Color,
+    metallic: f64,
+    roughness: f64,
+    specular_scale: f64,
+    sheen: f64,
+    sheen_tint: f64,
+    clearcoat: f64,
+    clearcoat_roughness: f64,
+    transmission: f64,
+    eta: f64,
+}
+
+#[derive(Clone, Copy)]
+enum Lobe {
+    Diffuse,
+    Specular,
+    Clearcoat,
+    Transmission,
+}
+
+impl Material for Principled {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        attenuation: &mut Color,
+        ray_scattered: &mut Ray,
+        sampler: &mut Sampler,
+    ) -> Option<ScatterKind> {
+        let ior = self.ior.value(hit_record);
+        let parameters = Parameters {
+            base_color: self.base_color.value(hit_record),
+            metallic: self.metallic.value(hit_record).clamp(0., 1.),
+            roughness: self.roughness.value(hit_record).clamp(0., 1.),
+            // A specular of 0.5 reproduces the reflectance of the index of refraction
+            specular_scale: f64::max(self.specular.value(hit_record), 0.) * 2.,
+            sheen: f64::max(self.sheen.value(hit_record), 0.),
+            sheen_tint: self.sheen_tint.value(hit_record).clamp(0., 1.),
+            clearcoat: self.clearcoat.value(hit_record).clamp(0., 1.),
+            clearcoat_roughness: self.clearcoat_roughness.value(hit_record).clamp(0., 1.),
+            transmission: self.transmission.value(hit_record).clamp(0., 1.),
+            // Index of refraction of the far side over the one of the side the ray comes from
+            eta: if hit_record.front_face {
+                ior / hit_record.outside_refraction_index
+            } else {
+                hit_record.outside_refraction_index / ior
+            },
+        };
+
+        let onb = Onb::from_normal(hit_record.normal);
+        let wo = onb.to_local(r_in.direction().unit_vector().inverted());
+        if wo.z() <= 0. {
+            return None;
+        }
+
+        // Pick one lobe proportional to how much it roughly reflects towards wo, its sample is
+        // divided by the probability of picking it.
+        let (weights, diffuse_color) = parameters.lobe_weights(wo.z(), hit_record.front_face);
+        let total: f64 = weights.iter().map(|(_, weight)| weight).sum();
+        if total <= 0. {
+            return None;
+        }
+
+        let mut choice = sampler.get_1d() * total;
+        let (lobe, weight) = *weights
+            .iter()
+            .find(|(_, weight)| {
+                choice -= weight;
+                choice < 0.
+            })
+            .unwrap_or(&weights[weights.len() - 1]);
+        let probability = weight / total;
+
+        let (wi, throughput, kind) = match lobe {
+            Lobe::Diffuse => {
+                let (u, v) = sampler.get_2d();
+                let mut wi = Vec3::new(0., 0., 1.) + Vec3::unit_vector_from_sample(u, v);
+                if wi.is_near_zero() {
+                    wi = Vec3::new(0., 0., 1.);
+                }
+                (wi, diffuse_color, ScatterKind::Diffuse)
+            }
+            Lobe::Specular => {
+                let distribution = TrowbridgeReitz::new(parameters.roughness, 0.);
+                let (u, v) = sampler.get_2d();
+                let wm = distribution.sample_visible_normal(wo, u, v);
+                let wi = wo.inverted().reflect(&wm);
+                if wi.z() <= 0. {
+                    return None;
+                }
+                let throughput = (1. - parameters.coat_reflectance(wo.z(), hit_record.front_face))
+                    * parameters.specular_reflectance(wo.dot(&wm))
+                    * distribution.g2(wo, wi)
+                    / distribution.g1(wo);
+                (wi, throughput, ScatterKind::Specular)
+            }
+            Lobe::Clearcoat => {
+                let distribution = TrowbridgeReitz::new(parameters.clearcoat_roughness, 0.);
+                let (u, v) = sampler.get_2d();
+                let wm = distribution.sample_visible_normal(wo, u, v);
+                let wi = wo.inverted().reflect(&wm);
+                if wi.z() <= 0. {
+                    return None;
+                }
+                let throughput = parameters.clearcoat
+                    * fresnel_schlick(Color::one() * 0.04, wo.dot(&wm))
+                    * distribution.g2(wo, wi)
+                    / distribution.g1(wo);
+                (wi, throughput, ScatterKind::Specular)
+            }
+            Lobe::Transmission => {
+                let distribution = TrowbridgeReitz::new(parameters.roughness, 0.);
+                let (u, v) = sampler.get_2d();
+                let wm = distribution.sample_visible_normal(wo, u, v);
+                let wi = refract(wo, wm, parameters.eta)?;
+                if wi.z() >= 0. {
+                    return None;
+                }
+                let throughput = (1. - parameters.coat_reflectance(wo.z(), hit_record.front_face))
+                    * (1. - parameters.metallic)
+                    * parameters.transmission
+                    * (1. - parameters.dielectric_reflectance(wo.dot(&wm)))
+                    * distribution.g2(wo, wi)
+                    / distribution.g1(wo)
+                    * parameters.base_color;
+                (wi, throughput, ScatterKind::Transmission)
+            }
+        };
+
+        *attenuation = throughput / probability;
+        *ray_scattered = Ray::new_with_time(hit_record.p, onb.to_world(wi), r_in.time());
+
+        Some(kind)
+    }
+
+    // Only surfaces that transmit can be entered, opaque ones never hide nested dielectrics
+    fn interior(&self, hit_record: &HitRecord, _wavelength: Option<f64>) -> Option<Interior> {
+        if self.transmission.value(hit_record) <= 0. {
+            return None;
+        }
+        Some(Interior {
+            object_id: hit_record.object_id,
+            priority: self.priority,
+            refraction_index: self.ior.value(hit_record),
+            absorption: Color::zero(),
+        })
+    }
+}
+
+impl Parameters {
+    fn dielectric_reflectance(&self, cos_theta: f64) -> f64 {
+        f64::min(
+            fresnel_dielectric(cos_theta, self.eta) * self.specular_scale,
+            1.,
+        )
+    }
+
+    fn specular_reflectance(&self, cos_theta: f64) -> Color {
+        let dielectric = Color::one() * self.dielectric_reflectance(cos_theta);
+        let metal = fresnel_schlick(self.base_color, cos_theta);
+        (1. - self.metallic) * dielectric + self.metallic * metal
+    }
+
+    // The coat only exists on the outside of the surface.
+    fn coat_reflectance(&self, cos_theta: f64, front_face: bool) -> f64 {
+        if !front_face {
+            return 0.;
+        }
+        self.clearcoat * fresnel_schlick(Color::one() * 0.04, cos_theta).x()
+    }
+
+    // Approximate reflectance of every lobe seen from an angle of `cos_theta` to the normal,
+    // along with the color of the diffuse lobe.
+    fn lobe_weights(&self, cos_theta: f64, front_face: bool) -> ([(Lobe, f64); 4], Color) {
+        let base = 1. - self.coat_reflectance(cos_theta, front_face);
+        let dielectric =
+            base * (1. - self.metallic) * (1. - self.dielectric_reflectance(cos_theta));
+
+        // Light inside a transmissive object never reaches the diffuse base or the sheen
+        let diffuse_color = if front_face {
+            let tint = if self.base_color.luminance() > 0. {
+                self.base_color / self.base_color.luminance()
+            } else {
+                Color::one()
+            };
+            let sheen_color = self.sheen
+                * ((1. - self.sheen_tint) * Color::one() + self.sheen_tint * tint)
+                * schlick_weight(cos_theta);
+            dielectric * ((1. - self.transmission) * self.base_color + sheen_color)
+        } else {
+            Color::zero()
+        };
+
+        let weights = [
+            (Lobe::Diffuse, diffuse_color.luminance()),
+            (
+                Lobe::Specular,
+                base * self.specular_reflectance(cos_theta).luminance(),
+            ),
+            (
+                Lobe::Clearcoat,
+                self.coat_reflectance(cos_theta, front_face),
+            ),
+            (
+                Lobe::Transmission,
+                dielectric * self.transmission * self.base_color.luminance(),
+            ),
+        ];
+
+        (weights, diffuse_color)
+    }
+}
@@ -0,0 +1,117 @@
+use math::{
+    onb::Onb,
+    vec3::{Color, Vec3},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{hittable::hittable::HitRecord, ray::Ray, sampler::sampler::Sampler};
+
+use super::{
+    dispersion::{refraction_index_at, Dispersion},
+    fresnel::fresnel_dielectric,
+    interior::{absolute_refraction_index, Absorption, Interior},
+    material::{Material, ScatterKind},
+    trowbridge_reitz::TrowbridgeReitz,
+};
+
+/// Frosted glass, the rough counterpart of `Dialectric` built on a GGX microfacet distribution.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub struct RoughDielectric {
+    pub refraction_index: f64,
+    /// Perceptual roughness, 0 behaves like `Dialectric`
+    pub roughness: f64,
+    #[serde(default)]
+    pub anisotropy: f64,
+    /// Replaces `refraction_index` in spectral mode
+    #[serde(default)]
+    pub dispersion: Option<Dispersion>,
+    #[serde(default)]
+    pub absorption: Option<Absorption>,
+    #[serde(default)]
+    pub priority: u32,
+}
+
+impl Material for RoughDielectric {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        attenuation: &mut Color,
+        ray_scattered: &mut Ray,
+        sampler: &mut Sampler,
+    ) -> Option<ScatterKind> {
+        let refraction_index = absolute_refraction_index(
+            refraction_index_at(self.refraction_index, self.dispersion, r_in.wavelength()),
+            hit_record.outside_refraction_index,
+        );
+        // Index of refraction of the far side over the one of the side the ray comes from
+        let eta = if hit_record.front_face {
+            refraction_index / hit_record.outside_refraction_index
+        } else {
+            hit_record.outside_refraction_index / refraction_index
+        };
+
+        let onb = Onb::from_normal(hit_record.normal);
+        let wo = onb.to_local(r_in.direction().unit_vector().inverted());
+        if wo.z() <= 0. {
+            return None;
+        }
+
+        let distribution = TrowbridgeReitz::new(self.roughness, self.anisotropy);
+        let (u, v) = sampler.get_2d();
+        let wm = distribution.sample_visible_normal(wo, u, v);
+
+        let reflectance = fresnel_dielectric(wo.dot(&wm), eta);
+
+        // Choose between reflection and refraction proportional to the fresnel term, which then
+        // cancels out of the sample weight
+        let (wi, kind) = match refract(wo, wm, eta) {
+            Some(refracted) if sampler.get_1d() >= reflectance => {
+                (refracted, ScatterKind::Transmission)
+            }
+            _ => (wo.inverted().reflect(&wm), ScatterKind::Specular),
+        };
+
+        let stays_above = wi.z() > 0.;
+        if stays_above != (kind == ScatterKind::Specular) {
+            return None;
+        }
+
+        *attenuation = Color::one() * distribution.g2(wo, wi) / distribution.g1(wo);
+        *ray_scattered = Ray::new_with_time(hit_record.p, onb.to_world(wi), r_in.time());
+
+        Some(kind)
+    }
+
+    fn is_dispersive(&self) -> bool {
+        self.dispersion.is_some()
+    }
+
+    fn interior(&self, hit_record: &HitRecord, wavelength: Option<f64>) -> Option<Interior> {
+        Some(Interior {
+            object_id: hit_record.object_id,
+            priority: self.priority,
+            refraction_index: absolute_refraction_index(
+                refraction_index_at(self.refraction_index, self.dispersion, wavelength),
+                hit_record.outside_refraction_index,
+            ),
+            absorption: self
+                .absorption
+                .map_or(Color::zero(), |absorption| absorption.coefficients()),
+        })
+    }
+}
+
+// Refracts `wo` (pointing away from the surface) through the microfacet with normal `wm`,
+// returns `None` on total internal reflection.
+pub(super) fn refract(wo: Vec3, wm: Vec3, eta: f64) -> Option<Vec3> {
+    let cos_theta_i = wo.dot(&wm);
+    let sin2_theta_i = f64::max(0., 1. - cos_theta_i * cos_theta_i);
+    let sin2_theta_t = sin2_theta_i / (eta * eta);
+    if sin2_theta_t >= 1. {
+        return None;
+    }
+
+    let cos_theta_t = f64::sqrt(1. - sin2_theta_t);
+    Some(wo.inverted() / eta + (cos_theta_i / eta - cos_theta_t) * wm)
+}
@@ -0,0 +1,221 @@
+use std::ops::{Add, Div, Mul, Sub};
+
+use math::vec3::Color;
+use serde::{Deserialize, Serialize};
+
+use crate::animation::AnimatedValue;
+
+/// Wavelengths in nanometers the interference is evaluated at for the red, green and blue
+/// channel.
+const CHANNEL_WAVELENGTHS: [f64; 3] = [650., 532., 450.];
+
+/// Thin transparent layer on top of a surface, like a soap film or an oil slick. Light reflected
+/// at its top and bottom interferes, which tints the reflection depending on the viewing angle.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub struct ThinFilm {
+    /// Thickness of the film in nanometers
+    pub thickness: AnimatedValue,
+    pub refraction_index: f64,
+}
+
+impl ThinFilm {
+    /// Reflectance of the film between a medium with index of refraction `outside` and a
+    /// substrate with the complex index of refraction `eta + i k` per color channel (Airy
+    /// summation of the internal reflections).
+    pub fn reflectance(
+        &self,
+        cos_theta_i: f64,
+        outside: f64,
+        eta: Color,
+        k: Color,
+        time: f64,
+    ) -> Color {
+        let thickness = f64::max(self.thickness.value_at_time(time), 0.);
+        let [eta, k] = [eta.to_array(), k.to_array()];
+        let channel = |i: usize| {
+            self.channel_reflectance(
+                cos_theta_i,
+                outside,
+                Complex::new(eta[i], k[i]),
+                thickness,
+                CHANNEL_WAVELENGTHS[i],
+            )
+        };
+        Color::new(channel(0), channel(1), channel(2))
+    }
+
+    fn channel_reflectance(
+        &self,
+        cos_theta_i: f64,
+        outside: f64,
+        substrate: Complex,
+        thickness: f64,
+        wavelength: f64,
+    ) -> f64 {
+        let cos1 = cos_theta_i.clamp(0., 1.);
+        let sin2_1 = 1. - cos1 * cos1;
+
+        let n1 = outside;
+        let n2 = self.refraction_index;
+        let sin2_2 = sin2_1 * (n1 / n2) * (n1 / n2);
+        if sin2_2 >= 1. {
+            // Total internal reflection at the top of the film
+            return 1.;
+        }
+        let cos2 = f64::sqrt(1. - sin2_2);
+
+        let n1 = Complex::real(n1);
+        let n2 = Complex::real(n2);
+        let n3 = substrate;
+        let cos1 = Complex::real(cos1);
+        let cos2 = Complex::real(cos2);
+        let cos3 = (Complex::real(1.) - Complex::real(sin2_1) * (n1 / n3) * (n1 / n3)).sqrt();
+
+        let phase =
+            4. * std::f64::consts::PI * self.refraction_index * thickness * cos2.re / wavelength;
+        let shift = Complex::new(phase.cos(), phase.sin());
+
+        let airy = |r12: Complex, r23: Complex| {
+            ((r12 + r23 * shift) / (Complex::real(1.) + r12 * r23 * shift)).norm_squared()
+        };
+
+        let r_s = airy(
+            (n1 * cos1 - n2 * cos2) / (n1 * cos1 + n2 * cos2),
+            (n2 * cos2 - n3 * cos3) / (n2 * cos2 + n3 * cos3),
+        );
+        let r_p = airy(
+            (n2 * cos1 - n1 * cos2) / (n2 * cos1 + n1 * cos2),
+            (n3 * cos2 - n2 * cos3) / (n3 * cos2 + n2 * cos3),
+        );
+
+        ((r_s + r_p) / 2.).clamp(0., 1.)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Complex {
+    re: f64,
+    im: f64,
+}
+
+impl Complex {
+    fn new(re: f64, im: f64) -> Self {
+        Self { re, im }
+    }
+
+    fn real(re: f64) -> Self {
+        Self::new(re, 0.)
+    }
+
+    fn norm_squared(&self) -> f64 {
+        self.re * self.re + self.im * self.im
+    }
+
+    // Principal square root
+    fn sqrt(&self) -> Self {
+        let norm = self.norm_squared().sqrt();
+        let re = f64::sqrt(f64::max((norm + self.re) / 2., 0.));
+        let im = f64::sqrt(f64::max((norm - self.re) / 2., 0.)).copysign(self.im);
+        Self::new(re, im)
+    }
+}
+
+impl Add for Complex {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self::new(self.re + other.re, self.im + other.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self::new(self.re - other.re, self.im - other.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        Self::new(
+            self.re * other.re - self.im * other.im,
+            self.re * other.im + self.im * other.re,
+        )
+    }
+}
+
+impl Div for Complex {
+    type Output = Self;
+
+    fn div(self, other: Self) -> Self {
+        let denominator = other.norm_squared();
+        Self::new(
+            (self.re * other.re + self.im * other.im) / denominator,
+            (self.im * other.re - self.re * other.im) / denominator,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::fresnel::{fresnel_conductor, fresnel_dielectric};
+
+    fn film(thickness: f64, refraction_index: f64) -> ThinFilm {
+        ThinFilm {
+            thickness: AnimatedValue::Static(thickness),
+            refraction_index,
+        }
+    }
+
+    fn reflectance(film: &ThinFilm, cos_theta: f64, eta: f64, k: f64) -> Color {
+        film.reflectance(cos_theta, 1., Color::one() * eta, Color::one() * k, 0.)
+    }
+
+    #[test]
+    fn vanishing_film_reflects_like_the_substrate() {
+        for cos_theta in [0.2, 0.6, 1.] {
+            let glass = reflectance(&film(0., 1.33), cos_theta, 1.5, 0.);
+            let expected = fresnel_dielectric(cos_theta, 1.5);
+            assert!((glass - Color::one() * expected).length() < 1e-9);
+
+            let gold = reflectance(&film(0., 1.33), cos_theta, 0.2, 3.);
+            let expected = fresnel_conductor(cos_theta, 0.2, 3.);
+            assert!((gold - Color::one() * expected).length() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn film_of_the_outside_index_changes_nothing() {
+        let with_film = reflectance(&film(350., 1.), 0.8, 1.5, 0.);
+        let expected = fresnel_dielectric(0.8, 1.5);
+        assert!((with_film - Color::one() * expected).length() < 1e-9);
+    }
+
+    #[test]
+    fn quarter_wave_film_cancels_the_reflection() {
+        // An anti reflective coating tuned to the wavelength of the green channel
+        let refraction_index = f64::sqrt(1.5);
+        let thickness = CHANNEL_WAVELENGTHS[1] / (4. * refraction_index);
+        let coated = reflectance(&film(thickness, refraction_index), 1., 1.5, 0.);
+        assert!(coated.y() < 1e-9);
+        assert!(coated.x() > coated.y() && coated.z() > coated.y());
+    }
+
+    #[test]
+    fn reflectance_stays_in_the_unit_interval() {
+        for thickness in [0., 120., 300., 780.] {
+            for cos_theta in [0., 0.1, 0.5, 1.] {
+                for (eta, k) in [(1.5, 0.), (1.1, 0.), (0.2, 3.)] {
+                    let r = reflectance(&film(thickness, 1.8), cos_theta, eta, k);
+                    for channel in r.to_array() {
+                        assert!((0. ..=1.).contains(&channel));
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use math::vec3::Vec3;
+
+/// Trowbridge-Reitz (GGX) microfacet distribution. All directions are in the local shading
+/// frame with the surface normal along z.
+#[derive(Debug, Clone, Copy)]
+pub struct TrowbridgeReitz {
+    alpha_x: f64,
+    alpha_y: f64,
+}
+
+impl TrowbridgeReitz {
+    /// Maps the perceptual `roughness` in [0, 1] to the distribution's alpha. `anisotropy` in
+    /// [0, 1) stretches the highlight along the first tangent.
+    pub fn new(roughness: f64, anisotropy: f64) -> Self {
+        let alpha = f64::max(roughness * roughness, 1e-3);
+        let aspect = f64::sqrt(1. - 0.9 * anisotropy.clamp(0., 1.));
+
+        Self {
+            alpha_x: f64::max(alpha / aspect, 1e-3),
+            alpha_y: f64::max(alpha * aspect, 1e-3),
+        }
+    }
+
+    // Auxiliary function of the Smith masking term.
+    fn lambda(&self, w: Vec3) -> f64 {
+        let cos2_theta = w.z() * w.z();
+        if cos2_theta == 0. {
+            return f64::INFINITY;
+        }
+
+        let alpha2_tan2_theta = (self.alpha_x * self.alpha_x * w.x() * w.x()
+            + self.alpha_y * self.alpha_y * w.y() * w.y())
+            / cos2_theta;
+        (f64::sqrt(1. + alpha2_tan2_theta) - 1.) / 2.
+    }
+
+    /// Fraction of microfacets visible from `w`.
+    pub fn g1(&self, w: Vec3) -> f64 {
+        1. / (1. + self.lambda(w))
+    }
+
+    /// Height correlated fraction of microfacets visible from both `wo` and `wi`.
+    pub fn g2(&self, wo: Vec3, wi: Vec3) -> f64 {
+        1. / (1. + self.lambda(wo) + self.lambda(wi))
+    }
+
+    /// Samples a microfacet normal proportional to its visible area from `wo` (Heitz,
+    /// "Sampling the GGX Distribution of Visible Normals"). `wo` has to be above the surface.
+    pub fn sample_visible_normal(&self, wo: Vec3, u: f64, v: f64) -> Vec3 {
+        // Stretch the view direction into the configuration of a hemisphere
+        let vh = Vec3::new(self.alpha_x * wo.x(), self.alpha_y * wo.y(), wo.z()).unit_vector();
+
+        let length_squared = vh.x() * vh.x() + vh.y() * vh.y();
+        let t1 = if length_squared > 0. {
+            Vec3::new(-vh.y(), vh.x(), 0.) / f64::sqrt(length_squared)
+        } else {
+            Vec3::new(1., 0., 0.)
+        };
+        let t2 = vh.cross(&t1);
+
+        // Uniform point on a disk, warped towards the visible half
+        let r = f64::sqrt(u);
+        let phi = 2. * std::f64::consts::PI * v;
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1. + vh.z());
+        let p2 = (1. - s) * f64::sqrt(1. - p1 * p1) + s * r * phi.sin();
+
+        let nh = p1 * t1 + p2 * t2 + f64::sqrt(f64::max(0., 1. - p1 * p1 - p2 * p2)) * vh;
+
+        // Unstretch back into the ellipsoid configuration
+        Vec3::new(
+            self.alpha_x * nh.x(),
+            self.alpha_y * nh.y(),
+            f64::max(1e-6, nh.z()),
+        )
+        .unit_vector()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use math::random::Pcg32;
+
+    use super::*;
+
+    fn directions() -> Vec<Vec3> {
+        let mut rng = Pcg32::new(42, 0);
+        (0..200)
+            .map(|_| {
+                let cos_theta = f64::max(rng.next_f64(), 1e-3);
+                let phi = 2. * std::f64::consts::PI * rng.next_f64();
+                let sin_theta = f64::sqrt(1. - cos_theta * cos_theta);
+                Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn masking_lies_in_the_unit_interval() {
+        let distribution = TrowbridgeReitz::new(0.6, 0.4);
+        let normal = Vec3::new(0., 0., 1.);
+        assert!((distribution.g1(normal) - 1.).abs() < 1e-12);
+
+        let directions = directions();
+        for (wo, wi) in directions.iter().zip(directions.iter().rev()) {
+            let (g1_wo, g1_wi) = (distribution.g1(*wo), distribution.g1(*wi));
+            assert!(g1_wo > 0. && g1_wo <= 1.);
+            // Masking and shadowing together never let more through than either of them
+            let g2 = distribution.g2(*wo, *wi);
+            assert!(g2 > 0. && g2 <= f64::min(g1_wo, g1_wi) + 1e-12);
+        }
+    }
+
+    #[test]
+    fn visible_normals_face_the_viewer() {
+        let mut rng = Pcg32::new(7, 0);
+        for roughness in [0., 0.3, 1.] {
+            let distribution = TrowbridgeReitz::new(roughness, 0.5);
+            for wo in directions() {
+                let wm = distribution.sample_visible_normal(wo, rng.next_f64(), rng.next_f64());
+                assert!((wm.length() - 1.).abs() < 1e-9);
+                assert!(wm.z() > 0.);
+                assert!(wo.dot(&wm) >= -1e-9, "{:?} sees {:?}", wo, wm);
+            }
+        }
+    }
+
+    #[test]
+    fn smooth_surfaces_sample_the_macro_normal() {
+        let distribution = TrowbridgeReitz::new(0., 0.);
+        let wo = Vec3::new(0.6, 0., 0.8);
+        let wm = distribution.sample_visible_normal(wo, 0.3, 0.7);
+        assert!(wm.z() > 0.999);
+    }
+}
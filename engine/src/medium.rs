@@ -0,0 +1,61 @@
+use math::{onb::Onb, vec3::Color, vec3::Vec3};
+use serde::{Deserialize, Serialize};
+
+/// Homogeneous participating medium such as fog, smoke or the inside of a translucent object.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct Medium {
+    /// Probability per unit of distance that a ray interacts with the medium
+    pub density: f64,
+    /// Fraction of the interacting light that is scattered instead of absorbed
+    pub albedo: Color,
+    #[serde(default)]
+    pub phase: PhaseFunction,
+}
+
+/// Distribution of the directions light is scattered into inside a medium.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize, Serialize)]
+#[serde(tag = "type")]
+pub enum PhaseFunction {
+    /// Scatters equally in all directions
+    #[default]
+    Isotropic,
+    /// `g` in (-1, 1) moves from back scattering over isotropic at 0 to forward scattering
+    HenyeyGreenstein { g: f64 },
+}
+
+impl Medium {
+    /// Distance the ray travels through the medium before it interacts with it, sampled from the
+    /// exponential falloff of the transmittance.
+    pub fn sample_distance(&self, u: f64) -> f64 {
+        if self.density <= 0. {
+            return f64::INFINITY;
+        }
+        -f64::ln(1. - u) / self.density
+    }
+}
+
+impl PhaseFunction {
+    /// Samples the direction a ray travelling along the unit vector `direction` continues in.
+    /// Both phase functions are sampled exactly, so the sample carries no additional weight.
+    pub fn sample(&self, direction: Vec3, u: f64, v: f64) -> Vec3 {
+        let g = match *self {
+            PhaseFunction::Isotropic => 0.,
+            PhaseFunction::HenyeyGreenstein { g } => g.clamp(-0.99, 0.99),
+        };
+
+        let cos_theta = if g.abs() < 1e-3 {
+            1. - 2. * u
+        } else {
+            let term = (1. - g * g) / (1. - g + 2. * g * u);
+            ((1. + g * g - term * term) / (2. * g)).clamp(-1., 1.)
+        };
+        let sin_theta = f64::sqrt(f64::max(0., 1. - cos_theta * cos_theta));
+        let phi = 2. * std::f64::consts::PI * v;
+
+        Onb::from_normal(direction).to_world(Vec3::new(
+            sin_theta * phi.cos(),
+            sin_theta * phi.sin(),
+            cos_theta,
+        ))
+    }
+}
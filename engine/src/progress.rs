@@ -0,0 +1,70 @@
+use serde::Serialize;
+
+/// What a render is doing, handed to the `ProgressReporter` of the frontend running it.
+#[derive(Serialize, Clone, Debug)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ProgressEvent {
+    /// The following frames are rendered with this camera
+    CameraStarted { camera: String },
+    /// Rendering of a frame starts, a resumed frame already has `passes_done` passes
+    FrameStarted {
+        frame: u32,
+        width: u32,
+        height: u32,
+        passes: u32,
+        passes_done: u32,
+        tiles: usize,
+    },
+    /// A tile of the current pass is finished, sent from the render threads
+    TileDone {
+        frame: u32,
+        pass: u32,
+        tiles_done: usize,
+        tiles: usize,
+        samples_done: u64,
+        samples: u64,
+        elapsed_in_seconds: f64,
+        /// Time until `samples_per_pixel` passes are done, or the time budget runs out
+        eta_in_seconds: Option<f64>,
+    },
+    /// No further passes are rendered, because all of them are done, a stop condition was
+    /// reached or the render was cancelled
+    FrameDone {
+        frame: u32,
+        passes: u32,
+        elapsed_in_seconds: f64,
+        cancelled: bool,
+    },
+    /// An image or video was written
+    Written { path: String },
+    /// Anything else worth telling the user about
+    Message { text: String },
+    /// The render goes on, but probably not the way it was meant to, e.g. images overwrite
+    /// each other
+    Warning { text: String },
+}
+
+/// Receives the progress of a render. `report` is called from the render threads, so it has to
+/// be cheap.
+pub trait ProgressReporter: Sync {
+    fn report(&self, event: ProgressEvent);
+
+    /// Asked between tiles, once it returns true the pass is abandoned and the render stops
+    /// with the passes finished so far.
+    fn is_cancelled(&self) -> bool {
+        false
+    }
+}
+
+/// Drops all progress.
+pub struct NoProgress;
+
+impl ProgressReporter for NoProgress {
+    fn report(&self, _event: ProgressEvent) {}
+}
+
+impl<F: Fn(ProgressEvent) + Sync> ProgressReporter for F {
+    fn report(&self, event: ProgressEvent) {
+        self(event)
+    }
+}
@@ -0,0 +1,207 @@
+use std::f64::consts::PI;
+
+use math::{circle::degrees_to_radians, vec3::Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::{lens::LensSystem, tile::Tile};
+
+/// How the camera maps the image onto rays.
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+#[serde(tag = "type")]
+pub enum Projection {
+    /// Thin lens camera with a vertical field of view of `vfov_in_degrees`
+    #[default]
+    Perspective,
+    /// Parallel rays through a viewport `height` world units tall
+    Orthographic { height: f64 },
+    /// Equidistant fisheye, the angle to the view direction grows linearly towards the edges and
+    /// reaches half of `fov_in_degrees` at the left and right border
+    Fisheye { fov_in_degrees: f64 },
+    /// 360° panorama with the longitude across the width and the latitude across the height,
+    /// meant for an aspect ratio of 2
+    Equirectangular,
+    /// Six 90° faces in a 3 by 2 grid, +x, -x, +y in the top row and -y, +z, -z in the bottom
+    /// one, meant for an aspect ratio of 1.5
+    CubeMap,
+    /// Traces the rays through a description of a real lens instead of the thin lens, with
+    /// `focus_dist` setting where the film is placed
+    Realistic(LensSystem),
+}
+
+impl Projection {
+    /// Parts of `image` that are separate images of their own, the faces of a cube map.
+    pub fn sub_images(&self, image: Tile) -> Vec<Tile> {
+        let Projection::CubeMap = self else {
+            return vec![image];
+        };
+        (0..2)
+            .flat_map(|row| (0..3).map(move |column| (row, column)))
+            .map(|(row, column)| Tile {
+                x0: image.x0 + image.width() * column / 3,
+                y0: image.y0 + image.height() * row / 2,
+                x1: image.x0 + image.width() * (column + 1) / 3,
+                y1: image.y0 + image.height() * (row + 1) / 2,
+            })
+            .collect()
+    }
+
+    /// Origin and direction of the ray through the image position `s`, `t` in [0, 1] from the
+    /// top left, in camera space with x to the right, y up and z pointing backwards. The origin
+    /// is only offset for orthographic cameras, the direction has unit length.
+    pub fn camera_ray(
+        &self,
+        s: f64,
+        t: f64,
+        vfov_in_degrees: f64,
+        aspect_ratio: f64,
+    ) -> (Vec3, Vec3) {
+        // Image position with x to the right and y up, [-1, 1] across the width
+        let x = 2. * s - 1.;
+        let y = (1. - 2. * t) / aspect_ratio;
+
+        match *self {
+            Projection::Perspective => {
+                let h = f64::tan(degrees_to_radians(vfov_in_degrees) / 2.);
+                let direction = Vec3::new(x * h * aspect_ratio, y * h * aspect_ratio, -1.);
+                (Vec3::zero(), direction.unit_vector())
+            }
+            Projection::Orthographic { height } => {
+                let half_width = height * aspect_ratio / 2.;
+                (
+                    Vec3::new(x * half_width, y * half_width, 0.),
+                    Vec3::new(0., 0., -1.),
+                )
+            }
+            Projection::Fisheye { fov_in_degrees } => {
+                let r = f64::sqrt(x * x + y * y);
+                let theta = r * degrees_to_radians(fov_in_degrees) / 2.;
+                if r == 0. {
+                    return (Vec3::zero(), Vec3::new(0., 0., -1.));
+                }
+                let direction = Vec3::new(theta.sin() * x / r, theta.sin() * y / r, -theta.cos());
+                (Vec3::zero(), direction)
+            }
+            Projection::Equirectangular => {
+                let longitude = (s - 0.5) * 2. * PI;
+                let latitude = (0.5 - t) * PI;
+                let direction = Vec3::new(
+                    latitude.cos() * longitude.sin(),
+                    latitude.sin(),
+                    -latitude.cos() * longitude.cos(),
+                );
+                (Vec3::zero(), direction)
+            }
+            Projection::Realistic(_) => {
+                unreachable!("Rays of a realistic lens are traced through its elements")
+            }
+            Projection::CubeMap => {
+                let column = f64::min((s * 3.).floor(), 2.);
+                let row = f64::min((t * 2.).floor(), 1.);
+                let a = 2. * (s * 3. - column) - 1.;
+                let b = 1. - 2. * (t * 2. - row);
+
+                // Forward, right and up of every face
+                let x = Vec3::new(1., 0., 0.);
+                let y = Vec3::new(0., 1., 0.);
+                let z = Vec3::new(0., 0., 1.);
+                let (forward, right, up) = match (row as u32, column as u32) {
+                    (0, 0) => (x, z, y),
+                    (0, 1) => (x.inverted(), z.inverted(), y),
+                    (0, _) => (y, x, z),
+                    (_, 0) => (y.inverted(), x, z.inverted()),
+                    (_, 1) => (z, x.inverted(), y),
+                    (_, _) => (z.inverted(), x, y),
+                };
+                (Vec3::zero(), (forward + a * right + b * up).unit_vector())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn direction(projection: &Projection, s: f64, t: f64) -> Vec3 {
+        projection.camera_ray(s, t, 90., 2.).1
+    }
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-9, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn image_centers_look_forward() {
+        let forward = Vec3::new(0., 0., -1.);
+        for projection in [
+            Projection::Perspective,
+            Projection::Orthographic { height: 2. },
+            Projection::Fisheye {
+                fov_in_degrees: 180.,
+            },
+            Projection::Equirectangular,
+        ] {
+            assert_close(direction(&projection, 0.5, 0.5), forward);
+        }
+    }
+
+    #[test]
+    fn perspective_spans_the_field_of_view() {
+        // 90° vertically at an aspect ratio of 2, the top edge is 45° up
+        let top = direction(&Projection::Perspective, 0.5, 0.);
+        assert_close(top, Vec3::new(0., 1., -1.).unit_vector());
+    }
+
+    #[test]
+    fn fisheye_angle_grows_linearly() {
+        let projection = Projection::Fisheye {
+            fov_in_degrees: 180.,
+        };
+        assert_close(direction(&projection, 1., 0.5), Vec3::new(1., 0., 0.));
+        assert_close(
+            direction(&projection, 0.75, 0.5),
+            Vec3::new(1., 0., -1.).unit_vector(),
+        );
+    }
+
+    #[test]
+    fn equirectangular_wraps_around() {
+        let projection = Projection::Equirectangular;
+        assert_close(direction(&projection, 0.75, 0.5), Vec3::new(1., 0., 0.));
+        assert_close(direction(&projection, 0., 0.5), Vec3::new(0., 0., 1.));
+        assert_close(direction(&projection, 0.3, 0.), Vec3::new(0., 1., 0.));
+    }
+
+    #[test]
+    fn cube_map_faces_look_along_the_axes() {
+        let projection = Projection::CubeMap;
+        let faces = [
+            ((1. / 6., 0.25), Vec3::new(1., 0., 0.)),
+            ((0.5, 0.25), Vec3::new(-1., 0., 0.)),
+            ((5. / 6., 0.25), Vec3::new(0., 1., 0.)),
+            ((1. / 6., 0.75), Vec3::new(0., -1., 0.)),
+            ((0.5, 0.75), Vec3::new(0., 0., 1.)),
+            ((5. / 6., 0.75), Vec3::new(0., 0., -1.)),
+        ];
+        for ((s, t), axis) in faces {
+            assert_close(projection.camera_ray(s, t, 90., 1.5).1, axis);
+        }
+    }
+
+    #[test]
+    fn cube_map_faces_are_separate_sub_images() {
+        let image = Tile {
+            x0: 0,
+            y0: 0,
+            x1: 300,
+            y1: 200,
+        };
+        let faces = Projection::CubeMap.sub_images(image);
+        assert_eq!(faces.len(), 6);
+        assert!(faces
+            .iter()
+            .all(|face| face.width() == 100 && face.height() == 100));
+        assert!(faces[4].contains((150, 150)));
+        assert_eq!(Projection::Perspective.sub_images(image), [image]);
+    }
+}
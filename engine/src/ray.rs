@@ -13,6 +13,9 @@ pub struct Ray {
     pub direction: Vec3,
 
     time: f64,
+    /// Wavelength in nanometers the ray is traced at in spectral mode
+    #[serde(default)]
+    wavelength: Option<f64>,
 }
 
 impl Ray {
@@ -31,6 +34,14 @@ impl Ray {
         self.time
     }
 
+    pub fn wavelength(&self) -> Option<f64> {
+        self.wavelength
+    }
+
+    pub fn with_wavelength(self, wavelength: Option<f64>) -> Self {
+        Self { wavelength, ..self }
+    }
+
     pub fn new(origin: Point3, direction: Vec3) -> Self {
         Self::new_with_time(origin, direction, 0.0)
     }
@@ -40,6 +51,7 @@ impl Ray {
             origin,
             direction,
             time,
+            wavelength: None,
         }
     }
 }
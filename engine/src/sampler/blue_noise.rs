@@ -0,0 +1,187 @@
+use std::sync::OnceLock;
+
+use math::random::{mix_seed, Pcg32};
+
+const SIZE: usize = 32;
+const SIGMA: f64 = 1.5;
+// Conjugate of the golden ratio, advances the value of a pixel from sample to sample
+const GOLDEN_RATIO_CONJUGATE: f64 = 0.618_033_988_749_895;
+
+/// Sample of the pixel's blue noise threshold advanced by the golden ratio sequence. Every
+/// dimension reads the mask at a different toroidal offset derived from the seed, so the
+/// dimensions, frames and seeds stay uncorrelated, while neighbouring pixels of one dimension
+/// keep the blue noise distribution.
+pub fn sample(pixel: (u32, u32), sample_index: u32, dimension: u32, seed: u64) -> f64 {
+    let offset = mix_seed(seed, dimension as u64);
+    let x = (pixel.0 as usize + (offset as usize % SIZE)) % SIZE;
+    let y = (pixel.1 as usize + ((offset >> 32) as usize % SIZE)) % SIZE;
+
+    let value = mask()[y * SIZE + x] + sample_index as f64 * GOLDEN_RATIO_CONJUGATE;
+    value - value.floor()
+}
+
+fn mask() -> &'static [f64] {
+    static MASK: OnceLock<Vec<f64>> = OnceLock::new();
+    MASK.get_or_init(void_and_cluster)
+}
+
+// Builds a tileable blue noise threshold mask with Ulichney's void-and-cluster method.
+fn void_and_cluster() -> Vec<f64> {
+    let count = SIZE * SIZE;
+
+    // Energy contribution of a point to every other cell, wrapping around the edges
+    let mut kernel = vec![0.; count];
+    for dy in 0..SIZE {
+        for dx in 0..SIZE {
+            let wx = dx.min(SIZE - dx) as f64;
+            let wy = dy.min(SIZE - dy) as f64;
+            kernel[dy * SIZE + dx] = f64::exp(-(wx * wx + wy * wy) / (2. * SIGMA * SIGMA));
+        }
+    }
+
+    let mut pattern = Pattern::new(kernel);
+
+    // Start with a random initial pattern of about a tenth of the cells
+    let mut rng = Pcg32::new(0, 0);
+    let initial_count = count / 10;
+    while pattern.ones < initial_count {
+        let cell = (rng.next_u32() as usize) % count;
+        if !pattern.cells[cell] {
+            pattern.toggle(cell);
+        }
+    }
+
+    // Move points from the tightest cluster into the largest void until the pattern is stable
+    loop {
+        let cluster = pattern.tightest_cluster();
+        pattern.toggle(cluster);
+        let void = pattern.largest_void();
+        if void == cluster {
+            pattern.toggle(cluster);
+            break;
+        }
+        pattern.toggle(void);
+    }
+
+    let mut ranks = vec![0usize; count];
+
+    // Rank the initial points by removing the tightest clusters first
+    let mut removing = pattern.clone();
+    for rank in (0..pattern.ones).rev() {
+        let cluster = removing.tightest_cluster();
+        removing.toggle(cluster);
+        ranks[cluster] = rank;
+    }
+
+    // Rank the remaining cells by filling the largest voids first
+    for rank in pattern.ones..count {
+        let void = pattern.largest_void();
+        pattern.toggle(void);
+        ranks[void] = rank;
+    }
+
+    ranks
+        .into_iter()
+        .map(|rank| (rank as f64 + 0.5) / count as f64)
+        .collect()
+}
+
+#[derive(Clone)]
+struct Pattern {
+    kernel: Vec<f64>,
+    cells: Vec<bool>,
+    energy: Vec<f64>,
+    ones: usize,
+}
+
+impl Pattern {
+    fn new(kernel: Vec<f64>) -> Self {
+        let count = kernel.len();
+        Self {
+            kernel,
+            cells: vec![false; count],
+            energy: vec![0.; count],
+            ones: 0,
+        }
+    }
+
+    fn toggle(&mut self, cell: usize) {
+        let sign = if self.cells[cell] { -1. } else { 1. };
+        self.cells[cell] = !self.cells[cell];
+        if self.cells[cell] {
+            self.ones += 1;
+        } else {
+            self.ones -= 1;
+        }
+
+        let (cx, cy) = (cell % SIZE, cell / SIZE);
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                let dx = (x + SIZE - cx) % SIZE;
+                let dy = (y + SIZE - cy) % SIZE;
+                self.energy[y * SIZE + x] += sign * self.kernel[dy * SIZE + dx];
+            }
+        }
+    }
+
+    fn tightest_cluster(&self) -> usize {
+        self.extreme_cell(true, |a, b| a > b)
+    }
+
+    fn largest_void(&self) -> usize {
+        self.extreme_cell(false, |a, b| a < b)
+    }
+
+    fn extreme_cell<F: Fn(f64, f64) -> bool>(&self, state: bool, better: F) -> usize {
+        let mut best = None;
+        for (cell, energy) in self.energy.iter().enumerate() {
+            if self.cells[cell] != state {
+                continue;
+            }
+            match best {
+                Some((_, best_energy)) if !better(*energy, best_energy) => {}
+                _ => best = Some((cell, *energy)),
+            }
+        }
+        best.map(|(cell, _)| cell).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mask_holds_every_threshold_once() {
+        let mut ranks: Vec<usize> = mask()
+            .iter()
+            .map(|value| (value * (SIZE * SIZE) as f64) as usize)
+            .collect();
+        ranks.sort();
+        assert_eq!(ranks, (0..SIZE * SIZE).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn low_thresholds_are_spread_out() {
+        // The first tenth of the points is well separated, unlike clumps of white noise
+        let points: Vec<(usize, usize)> = (0..SIZE * SIZE)
+            .filter(|&cell| mask()[cell] < 0.1)
+            .map(|cell| (cell % SIZE, cell / SIZE))
+            .collect();
+        for (i, a) in points.iter().enumerate() {
+            for b in &points[i + 1..] {
+                let dx = a.0.abs_diff(b.0).min(SIZE - a.0.abs_diff(b.0));
+                let dy = a.1.abs_diff(b.1).min(SIZE - a.1.abs_diff(b.1));
+                assert!(dx + dy > 1, "{:?} and {:?} are neighbours", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn samples_follow_the_golden_ratio_sequence() {
+        let first = sample((3, 4), 0, 2, 42);
+        let second = sample((3, 4), 1, 2, 42);
+        let step = (second - first).rem_euclid(1.);
+        assert!((step - GOLDEN_RATIO_CONJUGATE).abs() < 1e-12);
+    }
+}
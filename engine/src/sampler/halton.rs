@@ -0,0 +1,37 @@
+use math::random::mix_seed;
+
+use super::stratified::permute;
+
+const PRIMES: [u32; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131,
+];
+
+/// Owen scrambled radical inverse of the sample index in the prime base of the dimension. The
+/// digit permutations depend on the seed and on all preceding digits, so every pixel sees its own
+/// well stratified points even in the high bases. Returns `None` for dimensions beyond the prime
+/// table.
+pub fn sample(sample_index: u32, dimension: u32, seed: u64) -> Option<f64> {
+    let base = *PRIMES.get(dimension as usize)?;
+    Some(scrambled_radical_inverse(sample_index, base, seed))
+}
+
+fn scrambled_radical_inverse(mut index: u32, base: u32, seed: u64) -> f64 {
+    let inverse_base = 1.0 / base as f64;
+    let mut inverse_base_power = inverse_base;
+    let mut digit_seed = seed;
+    let mut result = 0.0;
+
+    // Trailing zero digits are scrambled as well, until they fall below double precision
+    while inverse_base_power > 1e-15 {
+        let digit = index % base;
+        let permuted_digit = permute(digit, base, digit_seed as u32);
+
+        result += permuted_digit as f64 * inverse_base_power;
+        inverse_base_power *= inverse_base;
+        index /= base;
+        digit_seed = mix_seed(digit_seed, digit as u64 + 1);
+    }
+
+    f64::min(result, 1.0 - f64::EPSILON)
+}
@@ -0,0 +1,6 @@
+pub mod blue_noise;
+pub mod halton;
+#[allow(clippy::module_inception)]
+pub mod sampler;
+pub mod sobol;
+pub mod stratified;
@@ -0,0 +1,230 @@
+use math::random::{mix_seed, Pcg32};
+use serde::{Deserialize, Serialize};
+
+use super::{blue_noise, halton, sobol, stratified};
+
+/// Sequence the sample values of a pixel are drawn from.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SamplerType {
+    /// Independent uniform random numbers
+    #[default]
+    Independent,
+    /// Every dimension is split into `samples_per_pixel` strata which are visited in a random
+    /// order (latin hypercube sampling)
+    Stratified,
+    /// Owen scrambled Halton sequence with its own scramble per pixel
+    Halton,
+    /// Owen scrambled Sobol sequence, dimension pairs are decorrelated by shuffling
+    Sobol,
+    /// Golden ratio sequence over a blue noise mask, distributing the remaining error as blue
+    /// noise between neighbouring pixels
+    BlueNoise,
+}
+
+/// Hands out the sample values of a single pixel sample. Every call consumes the next
+/// dimension, the camera uses the first five for the pixel position, the lens and the time,
+/// materials the following ones for their bounce decisions.
+pub struct Sampler {
+    sampler_type: SamplerType,
+    pixel: (u32, u32),
+    // Seed of the frame, shared by all pixels
+    seed: u64,
+    pixel_seed: u64,
+    sample_index: u32,
+    sample_count: u32,
+    dimension: u32,
+
+    // Used by the independent sampler and once the deterministic sequences run out of dimensions
+    rng: Pcg32,
+}
+
+impl Sampler {
+    pub fn new(
+        sampler_type: SamplerType,
+        pixel: (u32, u32),
+        image_width: u32,
+        sample_index: u32,
+        sample_count: u32,
+        seed: u64,
+    ) -> Self {
+        let pixel_index = pixel.1 as u64 * image_width as u64 + pixel.0 as u64;
+
+        Self {
+            sampler_type,
+            pixel,
+            seed,
+            pixel_seed: mix_seed(seed, pixel_index),
+            sample_index,
+            sample_count,
+            dimension: 0,
+            rng: Pcg32::new(mix_seed(seed, sample_index as u64), pixel_index),
+        }
+    }
+
+    /// Value in [0, 1) of the next dimension.
+    pub fn get_1d(&mut self) -> f64 {
+        let dimension = self.next_dimension();
+        self.sample(dimension)
+    }
+
+    /// Values in [0, 1)² of the next two dimensions.
+    pub fn get_2d(&mut self) -> (f64, f64) {
+        let dimension = self.next_dimension();
+        // Sobol pairs its two components, every other sequence treats them as separate dimensions
+        if self.sampler_type == SamplerType::Sobol {
+            return sobol::sample_2d(self.sample_index, dimension, self.pixel_seed);
+        }
+        let second_dimension = self.next_dimension();
+        (self.sample(dimension), self.sample(second_dimension))
+    }
+
+    fn next_dimension(&mut self) -> u32 {
+        let dimension = self.dimension;
+        self.dimension += 1;
+        dimension
+    }
+
+    fn sample(&mut self, dimension: u32) -> f64 {
+        let value = match self.sampler_type {
+            SamplerType::Independent => None,
+            SamplerType::Stratified => Some(stratified::sample(
+                self.sample_index,
+                self.sample_count,
+                mix_seed(self.pixel_seed, dimension as u64),
+                &mut self.rng,
+            )),
+            SamplerType::Halton => halton::sample(
+                self.sample_index,
+                dimension,
+                mix_seed(self.pixel_seed, dimension as u64),
+            ),
+            SamplerType::Sobol => {
+                Some(sobol::sample_2d(self.sample_index, dimension, self.pixel_seed).0)
+            }
+            SamplerType::BlueNoise => {
+                // The offsets must be the same for all pixels to keep the blue noise between them
+                Some(blue_noise::sample(
+                    self.pixel,
+                    self.sample_index,
+                    dimension,
+                    self.seed,
+                ))
+            }
+        };
+
+        value.unwrap_or_else(|| self.rng.next_f64())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TYPES: [SamplerType; 5] = [
+        SamplerType::Independent,
+        SamplerType::Stratified,
+        SamplerType::Halton,
+        SamplerType::Sobol,
+        SamplerType::BlueNoise,
+    ];
+
+    fn sampler(sampler_type: SamplerType, sample_index: u32, sample_count: u32) -> Sampler {
+        Sampler::new(sampler_type, (5, 3), 16, sample_index, sample_count, 42)
+    }
+
+    // Index of the stratum `value` falls into when [0, 1) is split into `count` strata
+    fn stratum(value: f64, count: u32) -> u32 {
+        (value * count as f64) as u32
+    }
+
+    #[test]
+    fn values_lie_in_the_unit_interval() {
+        for sampler_type in TYPES {
+            for sample_index in 0..64 {
+                let mut sampler = sampler(sampler_type, sample_index, 64);
+                // Past the dimensions the deterministic sequences cover
+                for _ in 0..40 {
+                    let x = sampler.get_1d();
+                    let (y, z) = sampler.get_2d();
+                    for value in [x, y, z] {
+                        assert!((0. ..1.).contains(&value), "{:?}: {}", sampler_type, value);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn same_sample_repeats_its_values() {
+        for sampler_type in TYPES {
+            let mut a = sampler(sampler_type, 3, 16);
+            let mut b = sampler(sampler_type, 3, 16);
+            for _ in 0..8 {
+                assert_eq!(a.get_1d(), b.get_1d());
+            }
+        }
+    }
+
+    #[test]
+    fn stratified_visits_every_stratum_of_every_dimension() {
+        let count = 16;
+        for dimension in 0..6 {
+            let mut strata: Vec<u32> = (0..count)
+                .map(|sample_index| {
+                    let mut sampler = sampler(SamplerType::Stratified, sample_index, count);
+                    (0..dimension).for_each(|_| {
+                        sampler.get_1d();
+                    });
+                    stratum(sampler.get_1d(), count)
+                })
+                .collect();
+            strata.sort();
+            assert_eq!(strata, (0..count).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn halton_stratifies_in_its_bases() {
+        // The first dimension is base 2, the second base 3
+        for (dimension, count) in [(0, 16), (1, 27)] {
+            let mut strata: Vec<u32> = (0..count)
+                .map(|sample_index| {
+                    let mut sampler = sampler(SamplerType::Halton, sample_index, count);
+                    (0..dimension).for_each(|_| {
+                        sampler.get_1d();
+                    });
+                    stratum(sampler.get_1d(), count)
+                })
+                .collect();
+            strata.sort();
+            assert_eq!(strata, (0..count).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn sobol_pairs_stratify_in_both_dimensions() {
+        let count = 16;
+        for dimension in 0..3 {
+            let points: Vec<(f64, f64)> = (0..count)
+                .map(|sample_index| {
+                    let mut sampler = sampler(SamplerType::Sobol, sample_index, count);
+                    (0..dimension).for_each(|_| {
+                        sampler.get_2d();
+                    });
+                    sampler.get_2d()
+                })
+                .collect();
+
+            let mut xs: Vec<u32> = points.iter().map(|p| stratum(p.0, count)).collect();
+            let mut ys: Vec<u32> = points.iter().map(|p| stratum(p.1, count)).collect();
+            let mut cells: Vec<u32> = points
+                .iter()
+                .map(|p| stratum(p.0, 4) * 4 + stratum(p.1, 4))
+                .collect();
+            for strata in [&mut xs, &mut ys, &mut cells] {
+                strata.sort();
+                assert_eq!(*strata, (0..count).collect::<Vec<_>>());
+            }
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use math::random::mix_seed;
+
+/// Point of the two dimensional Sobol (0, 2)-sequence with nested uniform (Owen) scrambling.
+/// Higher dimensions are built by padding: every dimension pair shuffles the sample index and
+/// scrambles with its own seed (Burley, "Practical Hash-based Owen Scrambling").
+pub fn sample_2d(sample_index: u32, dimension: u32, seed: u64) -> (f64, f64) {
+    let pair_seed = mix_seed(seed, dimension as u64);
+
+    let index = nested_uniform_scramble(sample_index, pair_seed as u32);
+    let x = nested_uniform_scramble(sobol(index, 0), (pair_seed >> 32) as u32);
+    let y = nested_uniform_scramble(sobol(index, 1), mix_seed(pair_seed, 1) as u32);
+
+    (to_unit_float(x), to_unit_float(y))
+}
+
+fn sobol(index: u32, dimension: u32) -> u32 {
+    let mut result = 0;
+    // The generator matrix of the first dimension is the bit reversal (van der Corput), the
+    // second one follows from the primitive polynomial x + 1.
+    let mut direction: u32 = 1 << 31;
+    let mut index = index;
+
+    while index != 0 {
+        if index & 1 == 1 {
+            result ^= direction;
+        }
+        direction = if dimension == 0 {
+            direction >> 1
+        } else {
+            direction ^ (direction >> 1)
+        };
+        index >>= 1;
+    }
+
+    result
+}
+
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    laine_karras_permutation(x.reverse_bits(), seed).reverse_bits()
+}
+
+// Hash in which every bit only depends on the bits below it.
+fn laine_karras_permutation(mut x: u32, seed: u32) -> u32 {
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50b47c);
+    x ^= x.wrapping_mul(0xb82f1e52);
+    x ^= x.wrapping_mul(0xc7afe638);
+    x ^= x.wrapping_mul(0x8d22f6e6);
+    x
+}
+
+fn to_unit_float(x: u32) -> f64 {
+    x as f64 / 4294967296.0
+}
@@ -0,0 +1,49 @@
+use math::random::Pcg32;
+
+/// Jittered sample within the stratum the sample index is mapped to. The mapping is a random
+/// permutation per pixel and dimension, so every dimension visits every stratum exactly once.
+pub fn sample(sample_index: u32, sample_count: u32, seed: u64, rng: &mut Pcg32) -> f64 {
+    let sample_count = sample_count.max(1);
+    let stratum = permute(sample_index % sample_count, sample_count, seed as u32);
+
+    (stratum as f64 + rng.next_f64()) / sample_count as f64
+}
+
+// Random permutation of [0, length) without storing it (Kensler, "Correlated Multi-Jittered
+// Sampling"). Values outside the range are cycled until they land inside.
+pub(crate) fn permute(index: u32, length: u32, seed: u32) -> u32 {
+    let mut mask = length - 1;
+    mask |= mask >> 1;
+    mask |= mask >> 2;
+    mask |= mask >> 4;
+    mask |= mask >> 8;
+    mask |= mask >> 16;
+
+    let mut i = index;
+    loop {
+        i ^= seed;
+        i = i.wrapping_mul(0xe170893d);
+        i ^= seed >> 16;
+        i ^= (i & mask) >> 4;
+        i ^= seed >> 8;
+        i = i.wrapping_mul(0x0929eb3f);
+        i ^= seed >> 23;
+        i ^= (i & mask) >> 1;
+        i = i.wrapping_mul(1 | seed >> 27);
+        i = i.wrapping_mul(0x6935fa69);
+        i ^= (i & mask) >> 11;
+        i = i.wrapping_mul(0x74dcb303);
+        i ^= (i & mask) >> 2;
+        i = i.wrapping_mul(0x9e501cc3);
+        i ^= (i & mask) >> 2;
+        i = i.wrapping_mul(0xc860a3df);
+        i &= mask;
+        i ^= i >> 5;
+
+        if i < length {
+            break;
+        }
+    }
+
+    (i.wrapping_add(seed)) % length
+}
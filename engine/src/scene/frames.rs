@@ -0,0 +1,180 @@
+use std::{collections::HashSet, fmt, str::FromStr};
+
+/// Most frames a parsed spec may list, which keeps a typo like `0-4294967295` from collecting
+/// billions of frames before the first one is rendered.
+pub const MAX_FRAMES: u64 = 1_000_000;
+
+/// Frames to render, written as a comma separated list of single frames `5`, ranges `1-100`
+/// including both ends, and ranges with a step `1-100x5` that render every fifth frame.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FrameSpec {
+    parts: Vec<FrameRange>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct FrameRange {
+    start: u32,
+    end: u32,
+    step: u32,
+}
+
+impl FrameRange {
+    fn len(&self) -> u64 {
+        (self.end - self.start) as u64 / self.step as u64 + 1
+    }
+}
+
+impl FrameSpec {
+    pub fn single(frame: u32) -> Self {
+        Self::range(frame, frame)
+    }
+
+    /// Frames `start` to `end`, including both.
+    pub fn range(start: u32, end: u32) -> Self {
+        Self {
+            parts: vec![FrameRange {
+                start,
+                end,
+                step: 1,
+            }],
+        }
+    }
+
+    /// The frames in the order they are listed, frames listed more than once are only
+    /// rendered the first time.
+    pub fn frames(&self) -> Vec<u32> {
+        let mut listed = HashSet::new();
+        self.parts
+            .iter()
+            .flat_map(|part| (part.start..=part.end).step_by(part.step as usize))
+            .filter(|frame| listed.insert(*frame))
+            .collect()
+    }
+}
+
+impl FromStr for FrameSpec {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let parse_frame = |frame: &str| {
+            frame
+                .trim()
+                .parse::<u32>()
+                .map_err(|error| format!("Invalid frame \"{}\": {}", frame.trim(), error))
+        };
+
+        let parts = spec
+            .split(',')
+            .map(|part| {
+                let (range, step) = match part.split_once('x') {
+                    Some((range, step)) => (range, parse_frame(step)?),
+                    None => (part, 1),
+                };
+                let (start, end) = match range.split_once('-') {
+                    Some((start, end)) => (parse_frame(start)?, parse_frame(end)?),
+                    None => {
+                        let frame = parse_frame(range)?;
+                        (frame, frame)
+                    }
+                };
+
+                if step == 0 {
+                    return Err(format!(
+                        "The step of \"{}\" must be at least 1",
+                        part.trim()
+                    ));
+                }
+                if start > end {
+                    return Err(format!(
+                        "The range \"{}\" ends before it starts",
+                        part.trim()
+                    ));
+                }
+                Ok(FrameRange { start, end, step })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let frames: u64 = parts.iter().map(FrameRange::len).sum();
+        if frames > MAX_FRAMES {
+            return Err(format!(
+                "\"{}\" lists {} frames, at most {} are supported",
+                spec.trim(),
+                frames,
+                MAX_FRAMES
+            ));
+        }
+
+        Ok(Self { parts })
+    }
+}
+
+impl fmt::Display for FrameSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, part) in self.parts.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{}", part.start)?;
+            if part.end != part.start {
+                write!(f, "-{}", part.end)?;
+            }
+            if part.step != 1 {
+                write!(f, "x{}", part.step)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranges_with_a_step() {
+        let spec: FrameSpec = "1-100x5".parse().unwrap();
+        let frames = spec.frames();
+        assert_eq!(frames.len(), 20);
+        assert_eq!(frames[..3], [1, 6, 11]);
+        assert_eq!(frames.last(), Some(&96));
+    }
+
+    #[test]
+    fn frames_listed_twice_are_rendered_once() {
+        let spec: FrameSpec = "1-3,2,5,3".parse().unwrap();
+        assert_eq!(spec.frames(), [1, 2, 3, 5]);
+    }
+
+    #[test]
+    fn ranges_must_not_end_before_they_start() {
+        assert!("5-1".parse::<FrameSpec>().is_err());
+    }
+
+    #[test]
+    fn specs_of_too_many_frames_are_rejected() {
+        assert!("0-4294967295".parse::<FrameSpec>().is_err());
+        assert!("0-4294967295x5000".parse::<FrameSpec>().is_ok());
+        assert!(format!("1-{}", MAX_FRAMES).parse::<FrameSpec>().is_ok());
+        assert!(format!("0-{}", MAX_FRAMES).parse::<FrameSpec>().is_err());
+        assert!(format!("1-{},0", MAX_FRAMES).parse::<FrameSpec>().is_err());
+    }
+
+    #[test]
+    fn steps_must_be_at_least_one() {
+        assert!("1-10x0".parse::<FrameSpec>().is_err());
+    }
+
+    #[test]
+    fn invalid_frames_are_rejected() {
+        assert!("a".parse::<FrameSpec>().is_err());
+        assert!("1-".parse::<FrameSpec>().is_err());
+        assert!("".parse::<FrameSpec>().is_err());
+    }
+
+    #[test]
+    fn display_round_trips() {
+        let spec: FrameSpec = "1-100x5, 7,9-12".parse().unwrap();
+        assert_eq!(spec.to_string(), "1-100x5,7,9-12");
+        assert_eq!(spec.to_string().parse::<FrameSpec>(), Ok(spec));
+    }
+}
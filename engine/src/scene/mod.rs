@@ -1 +1,4 @@
-pub mod scene;
\ No newline at end of file
+pub mod frames;
+pub mod output;
+#[allow(clippy::module_inception)]
+pub mod scene;
@@ -0,0 +1,224 @@
+use std::{fmt, path::Path, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use super::scene::DEFAULT_CAMERA;
+
+/// Name of the pass holding the rendered image.
+pub const BEAUTY_PASS: &str = "beauty";
+
+/// Path of the images written for every frame, relative to the directory of the scene file.
+/// The extension picks the image format. Tokens in braces are replaced per image:
+///
+/// - `{frame}` the frame number, `{frame:4}` pads it with zeros to at least 4 digits
+/// - `{camera}` the name of the camera
+/// - `{pass}` the name of the pass, `beauty` for the rendered image
+///
+/// `{{` and `}}` stand for literal braces. A checkpoint is kept next to the image of a frame
+/// while it renders, with the `.checkpoint` extension instead.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct OutputTemplate {
+    template: String,
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Text(String),
+    Frame { width: usize },
+    Camera,
+    Pass,
+}
+
+impl OutputTemplate {
+    /// Template used when the scene file doesn't set one. The camera name is put in front for
+    /// cameras other than the default one, and for all of them when `several_cameras` are
+    /// rendered so their images don't overwrite each other.
+    pub fn default_for_camera(camera_name: &str, several_cameras: bool) -> Self {
+        let template = if camera_name == DEFAULT_CAMERA && !several_cameras {
+            "output/frame-{frame:4}.png"
+        } else {
+            "output/{camera}-frame-{frame:4}.png"
+        };
+        template.parse().unwrap()
+    }
+
+    pub fn path(&self, frame: u32, camera_name: &str, pass: &str) -> String {
+        self.segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Text(text) => text.clone(),
+                Segment::Frame { width } => format!("{:0width$}", frame, width = width),
+                Segment::Camera => camera_name.to_string(),
+                Segment::Pass => pass.to_string(),
+            })
+            .collect()
+    }
+
+    /// Whether every frame gets its own path.
+    pub fn has_frame(&self) -> bool {
+        self.segments
+            .iter()
+            .any(|segment| matches!(segment, Segment::Frame { .. }))
+    }
+
+    /// Whether every camera gets its own path.
+    pub fn has_camera(&self) -> bool {
+        self.segments.contains(&Segment::Camera)
+    }
+}
+
+impl FromStr for OutputTemplate {
+    type Err = String;
+
+    fn from_str(template: &str) -> Result<Self, Self::Err> {
+        let mut segments = Vec::new();
+        let mut text = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut token = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => token.push(c),
+                            None => return Err(format!("Unclosed brace in \"{}\"", template)),
+                        }
+                    }
+                    let segment = match token.split_once(':') {
+                        None if token == "frame" => Segment::Frame { width: 0 },
+                        Some(("frame", width)) => Segment::Frame {
+                            width: width.parse().map_err(|_| {
+                                format!("Invalid padding \"{}\" of the frame", width)
+                            })?,
+                        },
+                        None if token == "camera" => Segment::Camera,
+                        None if token == "pass" => Segment::Pass,
+                        _ => {
+                            return Err(format!(
+                                "Unknown token {{{}}}, expected {{frame}}, {{camera}} or {{pass}}",
+                                token
+                            ))
+                        }
+                    };
+                    segments.push(Segment::Text(std::mem::take(&mut text)));
+                    segments.push(segment);
+                }
+                '}' => return Err(format!("Unmatched closing brace in \"{}\"", template)),
+                c => text.push(c),
+            }
+        }
+        segments.push(Segment::Text(text));
+        segments.retain(|segment| *segment != Segment::Text(String::new()));
+
+        if Path::new(template).extension().is_none() {
+            return Err(format!(
+                "The output \"{}\" needs an extension like .png for the image format",
+                template
+            ));
+        }
+
+        Ok(Self {
+            template: template.to_string(),
+            segments,
+        })
+    }
+}
+
+impl TryFrom<String> for OutputTemplate {
+    type Error = String;
+
+    fn try_from(template: String) -> Result<Self, Self::Error> {
+        template.parse()
+    }
+}
+
+impl From<OutputTemplate> for String {
+    fn from(template: OutputTemplate) -> Self {
+        template.template
+    }
+}
+
+impl fmt::Display for OutputTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.template)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(template: &str, frame: u32) -> String {
+        template
+            .parse::<OutputTemplate>()
+            .unwrap()
+            .path(frame, "side", BEAUTY_PASS)
+    }
+
+    #[test]
+    fn frames_are_padded() {
+        assert_eq!(path("frame-{frame:4}.png", 7), "frame-0007.png");
+        assert_eq!(path("frame-{frame}.png", 7), "frame-7.png");
+        assert_eq!(path("frame-{frame:2}.png", 1234), "frame-1234.png");
+    }
+
+    #[test]
+    fn camera_and_pass_are_replaced() {
+        assert_eq!(path("{camera}/{pass}-{frame}.exr", 3), "side/beauty-3.exr");
+    }
+
+    #[test]
+    fn double_braces_are_literal() {
+        assert_eq!(path("{{frame}}-{frame}.png", 3), "{frame}-3.png");
+        assert_eq!(path("a}}b.png", 3), "a}b.png");
+    }
+
+    #[test]
+    fn invalid_templates_are_rejected() {
+        for template in [
+            "{unknown}.png",
+            "{frame:x}.png",
+            "{frame.png",
+            "frame}.png",
+            "output/frame-{frame}",
+        ] {
+            assert!(
+                template.parse::<OutputTemplate>().is_err(),
+                "{} was accepted",
+                template
+            );
+        }
+    }
+
+    #[test]
+    fn tokens_are_detected() {
+        let template: OutputTemplate = "{camera}.png".parse().unwrap();
+        assert!(template.has_camera());
+        assert!(!template.has_frame());
+
+        let template: OutputTemplate = "{{camera}}-{frame}.png".parse().unwrap();
+        assert!(!template.has_camera());
+        assert!(template.has_frame());
+    }
+
+    #[test]
+    fn default_template_names_the_camera_when_needed() {
+        let default = |camera, several_cameras| {
+            OutputTemplate::default_for_camera(camera, several_cameras).has_camera()
+        };
+        assert!(!default(DEFAULT_CAMERA, false));
+        assert!(default(DEFAULT_CAMERA, true));
+        assert!(default("side", false));
+    }
+}
@@ -1,10 +1,26 @@
 use crate::{
     animation::{AnimatedValue, AnimatedVec3, AnimationContext},
-    camera::{Camera, CameraConfig},
-    hittable::hittable_list::HittableList,
+    camera::{Camera, CameraConfig, PathConfig, ProgressiveConfig, RenderEvent},
+    checkpoint::Checkpoint,
+    film::Film,
+    filter::Filter,
+    hittable::{hittable::Hittable, hittable_list::HittableList},
+    lens::LensConfig,
+    progress::{NoProgress, ProgressEvent, ProgressReporter},
+    projection::Projection,
+    sampler::sampler::SamplerType,
+    shutter::ShutterConfig,
+    texture::with_image_directory,
+    tile::{Tile, TileConfig},
+    video::{encode_video, VideoConfig, VideoFormat},
+};
+
+use super::{
+    frames::FrameSpec,
+    output::{OutputTemplate, BEAUTY_PASS},
 };
 use serde::{Deserialize, Serialize};
-use std::{fs, sync::Arc};
+use std::{collections::BTreeMap, fs, path::Path, sync::Arc};
 use toml;
 
 use crate::hittable::object::HittableObject;
@@ -15,134 +31,589 @@ use crate::material::metal::Metal;
 use crate::material::object::MaterialObject;
 
 use math::interval::Interval;
-use math::random::random_f64;
+use math::random::Pcg32;
 use math::vec3::{Color, Point3};
 
+#[derive(Clone)]
 pub enum RenderType {
     SingleFrame(u32),
     Animation(u32, u32),
+    Frames(FrameSpec),
+}
+
+impl RenderType {
+    pub fn frames(&self) -> Result<Vec<u32>, String> {
+        match self {
+            RenderType::SingleFrame(frame) => Ok(vec![*frame]),
+            RenderType::Animation(start_frame, frames) => {
+                let end_frame = start_frame.checked_add(*frames).ok_or_else(|| {
+                    format!(
+                        "The animation of {} frames from frame {} ends after the last frame {}",
+                        frames,
+                        start_frame,
+                        u32::MAX
+                    )
+                })?;
+                Ok((*start_frame..end_frame).collect())
+            }
+            RenderType::Frames(spec) => Ok(spec.frames()),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Scene {
     pub world: HittableList,
     camera: Camera,
+    camera_name: String,
+    // Configurations of all cameras, the one of the selected camera is only updated when another
+    // one is selected or the scene is saved
+    cameras: BTreeMap<String, CameraConfig>,
     directory: String,
+    // Template of the image paths, the default one depends on the camera
+    output: Option<OutputTemplate>,
+    video: Option<VideoConfig>,
+    resume: bool,
+    // Set while every camera is rendered one after the other
+    all_cameras: bool,
+    seed: u64,
+    overrides: RenderOverrides,
+}
+
+/// Settings of a single render invocation that take precedence over the camera of the scene
+/// file.
+#[derive(Debug, Clone, Default)]
+pub struct RenderOverrides {
+    pub image_width: Option<u32>,
+    pub aspect_ratio: Option<f64>,
+    pub samples_per_pixel: Option<u32>,
+    pub max_depth: Option<u32>,
+    /// Seed instead of the one of the scene file
+    pub seed: Option<u64>,
+    /// Only render this part of the image, in pixels of the full image
+    pub region: Option<Tile>,
+    /// Write only the region instead of the full image with the rest left black
+    pub crop: bool,
+    /// Template of the image paths instead of the one of the scene file
+    pub output: Option<OutputTemplate>,
+    /// Animation to assemble the frames into instead of the one of the scene file
+    pub video: Option<VideoConfig>,
+}
+
+impl RenderOverrides {
+    pub fn apply(&self, config: &mut CameraConfig) {
+        if let Some(image_width) = self.image_width {
+            config.image_width = image_width;
+        }
+        if let Some(aspect_ratio) = self.aspect_ratio {
+            config.aspect_ratio = aspect_ratio;
+        }
+        if let Some(samples_per_pixel) = self.samples_per_pixel {
+            config.samples_per_pixel = samples_per_pixel;
+        }
+        if let Some(max_depth) = self.max_depth {
+            config.max_depth = max_depth;
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize)]
 struct SceneConfig {
-    camera: CameraConfig,
+    /// Seed all random numbers of a render are derived from
+    #[serde(default)]
+    seed: u64,
+    /// Where the images are written, see `OutputTemplate`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    output: Option<OutputTemplate>,
+    /// Animated image the frames of an animation are assembled into
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    video: Option<VideoConfig>,
+    /// Camera used unless another one is selected, called "default"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    camera: Option<CameraConfig>,
+    /// Further cameras by name, each in a `[cameras.<name>]` table
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    cameras: BTreeMap<String, CameraConfig>,
     world: HittableList,
 }
 
 pub const SCENE_FILE_EXTENSION: &str = ".rrtscene";
 
+/// Name of the camera in the `[camera]` table of a scene file.
+pub const DEFAULT_CAMERA: &str = "default";
+
+/// Writes the film as an image, the format follows from the extension of `path`.
+pub fn save_film(film: &Film, path: &str) -> Result<(), String> {
+    // Create the image buffer
+    let mut imgbuf = image::ImageBuffer::new(film.width(), film.height());
+
+    // Iterate through the rendered pixel data and assign it to the image buffer
+    for (i, pixel) in film.to_pixels().iter().enumerate() {
+        let x = (i % film.width() as usize) as u32;
+        let y = (i / film.width() as usize) as u32;
+        let pixel = image::Rgb(*pixel); // Convert the pixel to the expected Rgb format
+        imgbuf.put_pixel(x, y, pixel);
+    }
+
+    imgbuf
+        .save(path)
+        .map_err(|error| format!("Failed to write image {}: {}", path, error))
+}
+
 impl Scene {
     pub fn new(world: HittableList, config: CameraConfig, directory: String) -> Self {
         Self {
             world,
-            camera: Camera::new_with_config(config),
+            camera: Camera::new_with_config(config.clone()),
+            camera_name: DEFAULT_CAMERA.to_string(),
+            cameras: BTreeMap::from([(DEFAULT_CAMERA.to_string(), config)]),
             directory,
+            output: None,
+            video: None,
+            resume: false,
+            all_cameras: false,
+            seed: 0,
+            overrides: RenderOverrides::default(),
         }
     }
 
-    pub fn save_config(&self, path: &str) {
-        // Check if the file extension is .toml
+    pub fn save_config(&self, path: &str) -> Result<(), String> {
         if !path.ends_with(SCENE_FILE_EXTENSION) {
-            panic!("The file extension must be .toml");
+            return Err(format!(
+                "{} is not a scene file, the file extension must be {}",
+                path, SCENE_FILE_EXTENSION
+            ));
         }
 
-        // Delete the file if it already exists
-        fs::remove_file(path).unwrap_or_default();
-
+        let mut cameras = self.cameras.clone();
+        cameras.insert(self.camera_name.clone(), self.camera.config.clone());
         let config = SceneConfig {
-            camera: self.camera.config,
+            seed: self.seed,
+            output: self.output.clone(),
+            video: self.video.clone(),
+            camera: cameras.remove(DEFAULT_CAMERA),
+            cameras,
             world: self.world.clone(),
         };
+        let contents = toml::to_string(&config)
+            .map_err(|error| format!("Failed to serialize the scene: {}", error))?;
 
         // create path if it doesn't exist
-        let parent = std::path::Path::new(path).parent().unwrap();
-        fs::create_dir_all(parent).unwrap();
+        if let Some(parent) = Path::new(path).parent() {
+            fs::create_dir_all(parent)
+                .map_err(|error| format!("Failed to create {}: {}", parent.display(), error))?;
+        }
 
-        fs::write(path, toml::to_string(&config).unwrap()).unwrap();
+        fs::write(path, contents).map_err(|error| format!("Failed to write {}: {}", path, error))
     }
 
+    /// Loads the scene file, or creates the example scene if it doesn't exist yet.
     pub fn load_config(scene_file_path: &str) -> Self {
-        // Check if the file extension is .toml
-        if !scene_file_path.ends_with(SCENE_FILE_EXTENSION) {
-            panic!("The file extension must be .toml");
+        Self::load_or_create(scene_file_path, &NoProgress)
+            .unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    /// Like `load_config`, but describes what is wrong with the scene file instead of panicking.
+    /// Creating the example scene is reported to `progress`.
+    pub fn load_or_create(
+        scene_file_path: &str,
+        progress: &dyn ProgressReporter,
+    ) -> Result<Self, String> {
+        if fs::metadata(scene_file_path).is_err() && scene_file_path.ends_with(SCENE_FILE_EXTENSION)
+        {
+            progress.report(ProgressEvent::Message {
+                text: "File not found. Creating example scene.".to_string(),
+            });
+            return Ok(Self::create_example_scene(Self::directory_of(
+                scene_file_path,
+            )));
         }
 
-        let directory = std::path::Path::new(scene_file_path)
-            .parent()
-            .unwrap()
-            .to_str()
-            .unwrap()
-            .to_string();
+        Self::from_file(scene_file_path)
+    }
 
-        if !fs::metadata(scene_file_path).is_ok() {
-            println!("File not found. Creating example scene.");
-            return Self::create_example_scene(directory);
+    /// Reads the scene file at `scene_file_path`, describing what is wrong with it otherwise.
+    pub fn from_file(scene_file_path: &str) -> Result<Self, String> {
+        if !scene_file_path.ends_with(SCENE_FILE_EXTENSION) {
+            return Err(format!(
+                "{} is not a scene file, the file extension must be {}",
+                scene_file_path, SCENE_FILE_EXTENSION
+            ));
         }
 
+        let contents = fs::read_to_string(scene_file_path)
+            .map_err(|error| format!("Failed to read {}: {}", scene_file_path, error))?;
         let config: SceneConfig =
-            toml::from_str(&fs::read_to_string(scene_file_path).unwrap()).unwrap();
+            with_image_directory(&Self::directory_of(scene_file_path), || {
+                toml::from_str(&contents)
+            })
+            .map_err(|error| format!("Failed to parse {}: {}", scene_file_path, error))?;
+
+        if let Some(video) = &config.video {
+            VideoFormat::from_path(&video.path.to_string())
+                .map_err(|error| format!("{}: {}", scene_file_path, error))?;
+        }
 
-        Self {
+        let mut cameras = config.cameras;
+        if let Some(camera) = config.camera {
+            cameras.insert(DEFAULT_CAMERA.to_string(), camera);
+        }
+        for (name, camera) in &cameras {
+            camera
+                .validate()
+                .map_err(|error| format!("{}: camera {}: {}", scene_file_path, name, error))?;
+        }
+        let camera_name = if cameras.contains_key(DEFAULT_CAMERA) {
+            DEFAULT_CAMERA.to_string()
+        } else {
+            match cameras.keys().next() {
+                Some(name) => name.clone(),
+                None => return Err(format!("{} has no camera", scene_file_path)),
+            }
+        };
+
+        Ok(Self {
             world: config.world,
-            camera: Camera::new_with_config(config.camera),
-            directory,
+            camera: Camera::new_with_config(cameras[&camera_name].clone()),
+            camera_name,
+            cameras,
+            directory: Self::directory_of(scene_file_path),
+            output: config.output,
+            video: config.video,
+            resume: false,
+            all_cameras: false,
+            seed: config.seed,
+            overrides: RenderOverrides::default(),
+        })
+    }
+
+    /// Directory the output of a scene file is written to, the one it is in.
+    pub fn directory_of(scene_file_path: &str) -> String {
+        match Path::new(scene_file_path).parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_string_lossy().to_string(),
+            _ => ".".to_string(),
         }
     }
 
-    /// Output path, scene path + output
-    pub fn output_path(&self) -> String {
-        self.directory.clone() + "/output"
+    /// Settings that take precedence over the scene file in the following renders, without
+    /// being saved to it.
+    pub fn set_overrides(&mut self, overrides: RenderOverrides) {
+        self.overrides = overrides;
     }
 
-    pub fn render_frame(&mut self, frame: u32) {
-        let world_arc = Arc::new(self.world.clone());
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    // Seed of the following renders, the override if any
+    fn render_seed(&self) -> u64 {
+        self.overrides.seed.unwrap_or(self.seed)
+    }
+
+    /// Continue frames from the checkpoints left behind by an interrupted render
+    pub fn set_resume(&mut self, resume: bool) {
+        self.resume = resume;
+    }
+
+    /// Names of all cameras of the scene.
+    pub fn camera_names(&self) -> Vec<String> {
+        self.cameras.keys().cloned().collect()
+    }
 
-        let rendered_frame = self.camera.render_frame(world_arc, frame);
+    pub fn camera_name(&self) -> &str {
+        &self.camera_name
+    }
 
-        // Create the image buffer
-        let mut imgbuf =
-            image::ImageBuffer::new(self.camera.config.image_width, self.camera.image_height());
+    /// Configuration of the camera called `name`.
+    pub fn camera_config(&self, name: &str) -> Option<&CameraConfig> {
+        if name == self.camera_name {
+            Some(&self.camera.config)
+        } else {
+            self.cameras.get(name)
+        }
+    }
 
-        // Iterate through the rendered pixel data and assign it to the image buffer
-        for (i, pixel) in rendered_frame.iter().enumerate() {
-            let x = (i % self.camera.config.image_width as usize) as u32;
-            let y = (i / self.camera.config.image_width as usize) as u32;
-            let pixel = image::Rgb(*pixel); // Convert the pixel to the expected Rgb format
-            imgbuf.put_pixel(x, y, pixel);
+    /// Renders the following frames with the camera called `name`.
+    pub fn select_camera(&mut self, name: &str) -> Result<(), String> {
+        if name == self.camera_name {
+            return Ok(());
         }
+        let Some(config) = self.cameras.get(name).cloned() else {
+            return Err(format!(
+                "The scene has no camera called {}. Available cameras: {}",
+                name,
+                self.camera_names().join(", ")
+            ));
+        };
+
+        self.cameras
+            .insert(self.camera_name.clone(), self.camera.config.clone());
+        self.camera = Camera::new_with_config(config);
+        self.camera_name = name.to_string();
+        Ok(())
+    }
+
+    /// Template of the image paths used by the following renders.
+    pub fn output_template(&self) -> OutputTemplate {
+        self.overrides
+            .output
+            .clone()
+            .or_else(|| self.output.clone())
+            .unwrap_or_else(|| {
+                OutputTemplate::default_for_camera(
+                    &self.camera_name,
+                    self.all_cameras && self.cameras.len() > 1,
+                )
+            })
+    }
+
+    /// Path of the image of `frame`, the template is relative to the directory of the scene.
+    pub fn image_path(&self, frame: u32) -> String {
+        let path = self
+            .output_template()
+            .path(frame, &self.camera_name, BEAUTY_PASS);
+        Path::new(&self.directory)
+            .join(path)
+            .to_string_lossy()
+            .to_string()
+    }
+
+    /// Renders `frame` and writes its image. A cancelled frame keeps its checkpoint, so it can
+    /// be resumed.
+    pub fn render_frame(
+        &mut self,
+        frame: u32,
+        progress: &dyn ProgressReporter,
+    ) -> Result<(), String> {
+        // Ids are assigned to the copy that is rendered, so objects added to the world since
+        // it was loaded get one as well
+        let mut world = self.world.clone();
+        world.assign_object_ids(&mut 1);
+        let world_arc = Arc::new(world);
+
+        let image_path = self.image_path(frame);
+        let checkpoint_path = Path::new(&image_path)
+            .with_extension("checkpoint")
+            .to_string_lossy()
+            .to_string();
 
         // Ensure the path exists
-        if !std::path::Path::new(&self.output_path()).exists() {
-            std::fs::create_dir_all(&self.output_path()).unwrap();
+        if let Some(parent) = Path::new(&image_path).parent() {
+            fs::create_dir_all(parent)
+                .map_err(|error| format!("Failed to create {}: {}", parent.display(), error))?;
         }
 
-        let image_path = format!("{}/frame-{}.png", self.output_path(), frame);
+        let mut camera = self.render_camera()?;
+        let checkpoint = self.load_checkpoint(&mut camera, &checkpoint_path, frame, progress)?;
+
+        let save_image = |film: &Film| match (self.overrides.crop, &self.overrides.region) {
+            (true, Some(region)) => save_film(&film.crop(region), &image_path),
+            _ => save_film(film, &image_path),
+        };
+        let save_checkpoint = |checkpoint: &Checkpoint| {
+            checkpoint.save(&checkpoint_path).map_err(|error| {
+                format!("Failed to write checkpoint {}: {}", checkpoint_path, error)
+            })
+        };
 
-        imgbuf.save(image_path).unwrap();
+        // Intermediate results are written to the final image path so long renders can be
+        // previewed while they are running. The render goes on if they fail, the first error
+        // is returned once it is done.
+        let mut intermediate_result = Ok(());
+        let checkpoint =
+            camera.render_frame_progressive(world_arc, checkpoint, progress, |event| {
+                let result = match event {
+                    RenderEvent::Preview(film) => save_image(film),
+                    RenderEvent::Checkpoint(checkpoint) => save_checkpoint(checkpoint),
+                };
+                if intermediate_result.is_ok() {
+                    intermediate_result = result;
+                }
+            })?;
+
+        let image_result = save_image(&checkpoint.film);
+        if image_result.is_ok() {
+            progress.report(ProgressEvent::Written {
+                path: image_path.clone(),
+            });
+        }
+
+        let checkpoint_result = if progress.is_cancelled() {
+            save_checkpoint(&checkpoint)
+        } else {
+            // The frame is done, nothing left to resume
+            fs::remove_file(&checkpoint_path).unwrap_or_default();
+            Ok(())
+        };
+
+        image_result.and(checkpoint_result).and(intermediate_result)
+    }
+
+    // Camera of the selected configuration with the overrides applied, which may make it
+    // invalid.
+    fn render_camera(&self) -> Result<Camera, String> {
+        let mut config = self.camera.config.clone();
+        self.overrides.apply(&mut config);
+        config.validate()?;
+
+        let mut camera = Camera::new_with_config(config);
+        camera.set_region(self.overrides.region)?;
+        Ok(camera)
+    }
+
+    fn load_checkpoint(
+        &self,
+        camera: &mut Camera,
+        checkpoint_path: &str,
+        frame: u32,
+        progress: &dyn ProgressReporter,
+    ) -> Result<Checkpoint, String> {
+        if self.resume && Path::new(checkpoint_path).exists() {
+            match Checkpoint::load(checkpoint_path) {
+                Ok(checkpoint)
+                    if checkpoint.frame == frame
+                        && checkpoint.seed == self.render_seed()
+                        && camera.can_resume(&self.world, &checkpoint)? =>
+                {
+                    progress.report(ProgressEvent::Message {
+                        text: format!(
+                            "Resuming frame {} after {} samples.",
+                            frame,
+                            checkpoint.film.passes()
+                        ),
+                    });
+                    return Ok(checkpoint);
+                }
+                Ok(_) => progress.report(ProgressEvent::Message {
+                    text: format!(
+                        "Checkpoint {} does not match the scene. Starting over.",
+                        checkpoint_path
+                    ),
+                }),
+                Err(error) => progress.report(ProgressEvent::Message {
+                    text: format!(
+                        "Failed to read checkpoint {}: {}. Starting over.",
+                        checkpoint_path, error
+                    ),
+                }),
+            }
+        }
+
+        camera.start_frame(&self.world, frame, self.render_seed())
     }
 
-    pub fn render_animation(&mut self, start_frame: u32, frames: u32) {
-        (start_frame..(start_frame + frames))
-            .into_iter()
-            .for_each(|frame| {
-                self.render_frame(frame);
+    pub fn render_animation(
+        &mut self,
+        start_frame: u32,
+        frames: u32,
+        progress: &dyn ProgressReporter,
+    ) -> Result<(), String> {
+        self.render(RenderType::Animation(start_frame, frames), progress)
+    }
+
+    /// Renders the frames one after the other until all are done, `progress` cancels the render
+    /// or writing an image fails.
+    pub fn render(
+        &mut self,
+        render_type: RenderType,
+        progress: &dyn ProgressReporter,
+    ) -> Result<(), String> {
+        let frames = render_type.frames()?;
+        progress.report(ProgressEvent::CameraStarted {
+            camera: self.camera_name.clone(),
+        });
+
+        if frames.len() > 1 && !self.output_template().has_frame() {
+            progress.report(ProgressEvent::Warning {
+                text: format!(
+                    "The output {} has no {{frame}}, every frame overwrites the previous one.",
+                    self.output_template()
+                ),
             });
+        }
+
+        for frame in &frames {
+            self.render_frame(*frame, progress)?;
+            if progress.is_cancelled() {
+                return Ok(());
+            }
+        }
+
+        self.write_video(&frames, progress)
     }
 
-    pub fn render(&mut self, render_type: RenderType) {
-        match render_type {
-            RenderType::SingleFrame(frame) => self.render_frame(frame),
-            RenderType::Animation(start_frame, frames) => {
-                self.render_animation(start_frame, frames)
+    // Video configured for the following renders, if any.
+    fn video(&self) -> Option<&VideoConfig> {
+        self.overrides.video.as_ref().or(self.video.as_ref())
+    }
+
+    // Assembles the rendered images of `frames` into the configured animation, if any. A
+    // single frame is no animation, which is reported instead.
+    fn write_video(&self, frames: &[u32], progress: &dyn ProgressReporter) -> Result<(), String> {
+        let Some(video) = self.video() else {
+            return Ok(());
+        };
+        if frames.len() < 2 {
+            progress.report(ProgressEvent::Warning {
+                text: format!(
+                    "Only one frame was rendered, the video {} is not written.",
+                    video.path
+                ),
+            });
+            return Ok(());
+        }
+
+        let images = frames
+            .iter()
+            .map(|frame| {
+                let path = self.image_path(*frame);
+                image::open(&path)
+                    .map(|image| image.into_rgb8())
+                    .map_err(|error| format!("Failed to read image {}: {}", path, error))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let path = video.path.path(frames[0], &self.camera_name, BEAUTY_PASS);
+        let path = Path::new(&self.directory)
+            .join(path)
+            .to_string_lossy()
+            .to_string();
+        let frames_per_second = self.camera.config.animation_meta.frames_per_second;
+        encode_video(&images, frames_per_second, video, &path)?;
+
+        progress.report(ProgressEvent::Written { path });
+        Ok(())
+    }
+
+    /// Renders the frames with every camera of the scene one after the other.
+    pub fn render_all_cameras(
+        &mut self,
+        render_type: RenderType,
+        progress: &dyn ProgressReporter,
+    ) -> Result<(), String> {
+        // The default template has the camera name while several cameras are rendered
+        let output = self.overrides.output.as_ref().or(self.output.as_ref());
+        if let Some(output) = output.filter(|output| self.cameras.len() > 1 && !output.has_camera())
+        {
+            progress.report(ProgressEvent::Warning {
+                text: format!(
+                    "The output {} has no {{camera}}, every camera overwrites the previous one.",
+                    output
+                ),
+            });
+        }
+
+        self.all_cameras = true;
+        let mut result = Ok(());
+        for name in self.camera_names() {
+            self.select_camera(&name)?;
+            result = self.render(render_type.clone(), progress);
+            if result.is_err() || progress.is_cancelled() {
+                break;
             }
         }
+        self.all_cameras = false;
+        result
     }
 
     pub fn create_example_scene(directory: String) -> Scene {
@@ -166,6 +637,18 @@ impl Scene {
 
                 defocus_angle_in_degrees: 0.6,
                 focus_dist: 10.,
+
+                progressive: ProgressiveConfig::default(),
+                tiles: TileConfig::default(),
+                sampler: SamplerType::default(),
+                filter: Filter::default(),
+                path: PathConfig::default(),
+                fog: None,
+                spectral: false,
+                projection: Projection::default(),
+                stereo: None,
+                lens: LensConfig::default(),
+                shutter: ShutterConfig::default(),
             },
             directory,
         );
@@ -173,50 +656,54 @@ impl Scene {
         let ground_material = MaterialObject::Lambertian(Lambertian {
             albedo: Color::new(0.5, 0.5, 0.5),
         });
-        scene.world.add(HittableObject::Sphere(Sphere::new(
+        scene.world.add(HittableObject::Sphere(Box::new(Sphere::new(
             AnimatedVec3::static_value(Point3::new(0.0, -1000.0, 0.0)),
             1000.0,
             ground_material,
-        )));
+        ))));
+
+        // The example world is derived from the scene seed as well, so it is the same every time
+        let mut rng = Pcg32::new(scene.seed, 0);
 
         fn create_animated_vec3(
             center_point: Point3,
             frequency_interval: Interval,
             amplitude_interval: Interval,
             phase_shift_interval: Interval,
+            rng: &mut Pcg32,
         ) -> AnimatedVec3 {
             AnimatedVec3 {
                 x: AnimatedValue::Sinusoidal {
                     baseline: center_point.x(),
-                    frequency: frequency_interval.random(),
-                    amplitude: amplitude_interval.random(),
-                    phase_shift: phase_shift_interval.random(),
+                    frequency: frequency_interval.random(rng),
+                    amplitude: amplitude_interval.random(rng),
+                    phase_shift: phase_shift_interval.random(rng),
                 },
                 y: AnimatedValue::Sinusoidal {
                     baseline: center_point.y(),
-                    frequency: frequency_interval.random(),
-                    amplitude: amplitude_interval.random(),
-                    phase_shift: phase_shift_interval.random(),
+                    frequency: frequency_interval.random(rng),
+                    amplitude: amplitude_interval.random(rng),
+                    phase_shift: phase_shift_interval.random(rng),
                 },
                 z: AnimatedValue::Sinusoidal {
                     baseline: center_point.z(),
-                    frequency: frequency_interval.random(),
-                    amplitude: amplitude_interval.random(),
-                    phase_shift: phase_shift_interval.random(),
+                    frequency: frequency_interval.random(rng),
+                    amplitude: amplitude_interval.random(rng),
+                    phase_shift: phase_shift_interval.random(rng),
                 },
             }
         }
 
         for a in -11..11 {
             for b in -11..11 {
-                let choose_mat = random_f64();
+                let choose_mat = rng.next_f64();
                 let center_point = Point3::new(
-                    a as f64 + 0.9 * random_f64(),
+                    a as f64 + 0.9 * rng.next_f64(),
                     0.2,
-                    b as f64 + 0.9 * random_f64(),
+                    b as f64 + 0.9 * rng.next_f64(),
                 );
 
-                let center = if random_f64() < 0.8 {
+                let center = if rng.next_f64() < 0.8 {
                     let frequency_interval = Interval::new(1.0, 4.0);
                     let amplitude_interval = Interval::new(0.1, 0.3);
                     let phase_shift_interval = Interval::new(0.0, 2.0 * std::f64::consts::PI);
@@ -225,6 +712,7 @@ impl Scene {
                         frequency_interval,
                         amplitude_interval,
                         phase_shift_interval,
+                        &mut rng,
                     )
                 } else {
                     AnimatedVec3::static_value(center_point)
@@ -234,42 +722,54 @@ impl Scene {
                     // let sphere_material: Arc<dyn ray_tracer::materials::Material>;
                     if choose_mat < 0.8 {
                         // diffuse
-                        let albedo = Color::random() * Color::random();
+                        let albedo = Color::random(&mut rng) * Color::random(&mut rng);
                         let sphere_material = MaterialObject::Lambertian(Lambertian { albedo });
-                        scene.world.add(HittableObject::Sphere(Sphere::new(
+                        scene.world.add(HittableObject::Sphere(Box::new(Sphere::new(
                             center,
                             0.2,
                             sphere_material,
-                        )));
+                        ))));
                     } else if choose_mat < 0.95 {
                         // metal
-                        let albedo = Color::random_interval(Interval::new(0.5, 1.0));
-                        let fuzz = Interval::new(0.0, 0.5).random();
-                        let sphere_material = MaterialObject::Metal(Metal { albedo, fuzz });
-                        scene.world.add(HittableObject::Sphere(Sphere::new(
+                        let albedo = Color::random_interval(Interval::new(0.5, 1.0), &mut rng);
+                        let fuzz = Interval::new(0.0, 0.5).random(&mut rng);
+                        let sphere_material = MaterialObject::Metal(Metal {
+                            albedo,
+                            fuzz,
+                            thin_film: None,
+                        });
+                        scene.world.add(HittableObject::Sphere(Box::new(Sphere::new(
                             center,
                             0.2,
                             sphere_material,
-                        )));
+                        ))));
                     } else {
                         // glass
                         let glass_outer_mat = MaterialObject::Dielectric(Dialectric {
                             refraction_index: 1.5,
+                            dispersion: None,
+                            absorption: None,
+                            priority: 0,
+                            thin_film: None,
                         });
                         let glass_inner_mat = MaterialObject::Dielectric(Dialectric {
-                            refraction_index: 1. / 1.5,
+                            refraction_index: 1.0,
+                            dispersion: None,
+                            absorption: None,
+                            priority: 1,
+                            thin_film: None,
                         });
 
-                        scene.world.add(HittableObject::Sphere(Sphere::new(
+                        scene.world.add(HittableObject::Sphere(Box::new(Sphere::new(
                             center,
                             0.2,
                             glass_outer_mat,
-                        )));
-                        scene.world.add(HittableObject::Sphere(Sphere::new(
+                        ))));
+                        scene.world.add(HittableObject::Sphere(Box::new(Sphere::new(
                             center,
                             0.1,
                             glass_inner_mat,
-                        )));
+                        ))));
                     }
                 }
             }
@@ -277,50 +777,79 @@ impl Scene {
 
         let material1 = MaterialObject::Dielectric(Dialectric {
             refraction_index: 1.5,
+            dispersion: None,
+            absorption: None,
+            priority: 0,
+            thin_film: None,
         });
         let material1_inner = MaterialObject::Dielectric(Dialectric {
-            refraction_index: 1. / 1.5,
+            refraction_index: 1.0,
+            dispersion: None,
+            absorption: None,
+            priority: 1,
+            thin_film: None,
         });
-        scene.world.add(HittableObject::Sphere(Sphere::new(
+        scene.world.add(HittableObject::Sphere(Box::new(Sphere::new(
             AnimatedVec3::static_value(Point3::new(0.0, 1.0, 0.0)),
             1.0,
             material1,
-        )));
-        scene.world.add(HittableObject::Sphere(Sphere::new(
+        ))));
+        scene.world.add(HittableObject::Sphere(Box::new(Sphere::new(
             AnimatedVec3::static_value(Point3::new(0.0, 1.0, 0.0)),
             0.9,
             material1_inner,
-        )));
+        ))));
 
         let material2 = MaterialObject::Lambertian(Lambertian {
             albedo: Color::new(0.4, 0.2, 0.1),
         });
-        scene.world.add(HittableObject::Sphere(Sphere::new(
+        scene.world.add(HittableObject::Sphere(Box::new(Sphere::new(
             create_animated_vec3(
                 Point3::new(-4.0, 1.0, 0.0),
                 Interval::new(1.0, 4.0),
                 Interval::new(0.1, 0.3),
                 Interval::new(0.0, 2.0 * std::f64::consts::PI),
+                &mut rng,
             ),
             1.0,
             material2,
-        )));
+        ))));
 
         let material3 = MaterialObject::Metal(Metal {
             albedo: Color::new(0.7, 0.6, 0.5),
             fuzz: 0.0,
+            thin_film: None,
         });
-        scene.world.add(HittableObject::Sphere(Sphere::new(
+        scene.world.add(HittableObject::Sphere(Box::new(Sphere::new(
             create_animated_vec3(
                 Point3::new(4.0, 1.0, 0.0),
                 Interval::new(1.0, 1.0),
                 Interval::new(0.1, 0.15),
                 Interval::new(0.0, 2.0 * std::f64::consts::PI),
+                &mut rng,
             ),
             1.0,
             material3,
-        )));
+        ))));
+
+        scene
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn animations_list_their_frames() {
+        assert_eq!(RenderType::Animation(3, 4).frames(), Ok(vec![3, 4, 5, 6]));
+        assert_eq!(RenderType::Animation(3, 0).frames(), Ok(vec![]));
+        assert_eq!(RenderType::SingleFrame(7).frames(), Ok(vec![7]));
+    }
 
-        return scene;
+    #[test]
+    fn animations_past_the_last_frame_are_rejected() {
+        assert!(RenderType::Animation(u32::MAX, 2).frames().is_err());
+        assert!(RenderType::Animation(u32::MAX - 1, 1).frames().is_ok());
     }
 }
@@ -0,0 +1,188 @@
+use serde::{Deserialize, Serialize};
+
+/// When and how the shutter opens during a frame, the `shutter_speed` of the animation sets how
+/// long it stays open.
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct ShutterConfig {
+    /// Where the shutter opens relative to the time of the frame, in multiples of the shutter
+    /// speed. 0 opens it at the frame time, -0.5 centers the exposure on it.
+    pub offset: f64,
+    /// How far the shutter is open over the exposure, only shapes the motion blur and leaves the
+    /// brightness of the image unchanged
+    pub curve: ShutterCurve,
+    /// Time in seconds the shutter takes to sweep from the top to the bottom row of the image,
+    /// every row is exposed for the shutter speed from when the sweep reaches it. Negative
+    /// values sweep from the bottom up, 0 exposes all rows at once.
+    pub rolling_shutter_duration: f64,
+}
+
+/// Efficiency of the shutter over the exposure, from fully closed at 0 to fully open at 1.
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+#[serde(tag = "type")]
+pub enum ShutterCurve {
+    /// Fully open for the whole exposure
+    #[default]
+    Box,
+    /// Opens and closes linearly, over the given fractions of the exposure
+    Trapezoid { opening: f64, closing: f64 },
+    /// Efficiency at evenly spaced points from the start to the end of the exposure, linearly
+    /// interpolated in between
+    Tabulated { values: Vec<f64> },
+}
+
+impl ShutterConfig {
+    /// Time a ray is traced at for the row at `row` from the top of the image, in [0, 1], given
+    /// the uniform sample `u`.
+    pub fn sample_time(&self, frame_time: f64, shutter_speed: f64, row: f64, u: f64) -> f64 {
+        let sweep = if self.rolling_shutter_duration < 0. {
+            1. - row
+        } else {
+            row
+        };
+        let open =
+            frame_time + self.offset * shutter_speed + self.rolling_shutter_duration.abs() * sweep;
+
+        open + shutter_speed * self.curve.sample(u)
+    }
+}
+
+impl ShutterCurve {
+    /// Maps the uniform sample `u` to a point in [0, 1] of the exposure, distributed
+    /// proportional to the efficiency.
+    pub fn sample(&self, u: f64) -> f64 {
+        match self {
+            ShutterCurve::Box => u,
+            ShutterCurve::Trapezoid { opening, closing } => {
+                let opening = opening.clamp(0., 1.);
+                let closing = closing.clamp(0., 1. - opening);
+                Self::sample_piecewise_linear(
+                    &[(0., 0.), (opening, 1.), (1. - closing, 1.), (1., 0.)],
+                    u,
+                )
+            }
+            ShutterCurve::Tabulated { values } => {
+                if values.len() < 2 {
+                    return u;
+                }
+                let last = (values.len() - 1) as f64;
+                let points: Vec<(f64, f64)> = values
+                    .iter()
+                    .enumerate()
+                    .map(|(i, value)| (i as f64 / last, f64::max(*value, 0.)))
+                    .collect();
+                Self::sample_piecewise_linear(&points, u)
+            }
+        }
+    }
+
+    // Inverts the integral of the piecewise linear function through `points`, which are sorted
+    // by their position. Falls back to uniform sampling if the function is zero everywhere.
+    fn sample_piecewise_linear(points: &[(f64, f64)], u: f64) -> f64 {
+        let areas: Vec<f64> = points
+            .windows(2)
+            .map(|segment| (segment[1].0 - segment[0].0) * (segment[0].1 + segment[1].1) / 2.)
+            .collect();
+        let total: f64 = areas.iter().sum();
+        if total <= 0. {
+            return u;
+        }
+
+        let mut target = u * total;
+        for (i, (segment, area)) in points.windows(2).zip(&areas).enumerate() {
+            if target > *area && i + 1 < areas.len() {
+                target -= area;
+                continue;
+            }
+
+            // Solve the area under the line from the start of the segment for the position,
+            // (y0 + slope * x / 2) * x = target
+            let ((x0, y0), (x1, y1)) = (segment[0], segment[1]);
+            let width = x1 - x0;
+            if width <= 0. {
+                return x0;
+            }
+            let slope = (y1 - y0) / width;
+            let x = if slope.abs() < 1e-12 {
+                if y0 > 0. {
+                    target / y0
+                } else {
+                    0.
+                }
+            } else {
+                (-y0 + f64::sqrt(f64::max(y0 * y0 + 2. * slope * target, 0.))) / slope
+            };
+            return x0 + x.clamp(0., width);
+        }
+
+        u
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{} is not {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn constant_function_samples_uniformly() {
+        for u in [0., 0.25, 0.5, 1.] {
+            assert_near(
+                ShutterCurve::sample_piecewise_linear(&[(0., 2.), (1., 2.)], u),
+                u,
+            );
+        }
+    }
+
+    #[test]
+    fn zero_function_falls_back_to_uniform() {
+        assert_near(
+            ShutterCurve::sample_piecewise_linear(&[(0., 0.), (1., 0.)], 0.3),
+            0.3,
+        );
+    }
+
+    #[test]
+    fn ramp_inverts_its_integral() {
+        // The integral of x is x² / 2, normalized u = x²
+        for u in [0., 0.25, 0.5, 1.] {
+            assert_near(
+                ShutterCurve::sample_piecewise_linear(&[(0., 0.), (1., 1.)], u),
+                f64::sqrt(u),
+            );
+        }
+    }
+
+    #[test]
+    fn samples_span_several_segments() {
+        let points = [(0., 0.), (0.25, 1.), (0.75, 1.), (1., 0.)];
+        assert_near(ShutterCurve::sample_piecewise_linear(&points, 0.5), 0.5);
+        // The first ramp holds a sixth of the area
+        assert_near(
+            ShutterCurve::sample_piecewise_linear(&points, 1. / 6.),
+            0.25,
+        );
+
+        let mut previous = 0.;
+        for step in 0..=100 {
+            let t = ShutterCurve::sample_piecewise_linear(&points, step as f64 / 100.);
+            assert!((0. ..=1.).contains(&t));
+            assert!(t >= previous);
+            previous = t;
+        }
+    }
+
+    #[test]
+    fn zero_width_segments_are_skipped() {
+        let points = [(0., 1.), (0.5, 1.), (0.5, 1.), (1., 1.)];
+        assert_near(ShutterCurve::sample_piecewise_linear(&points, 0.75), 0.75);
+    }
+}
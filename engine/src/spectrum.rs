@@ -0,0 +1,131 @@
+use std::sync::OnceLock;
+
+use math::vec3::{Color, Vec3};
+
+/// Range of visible wavelengths in nanometers the spectral integrator samples.
+pub const LAMBDA_MIN: f64 = 360.;
+pub const LAMBDA_MAX: f64 = 830.;
+
+/// Wavelengths a path is traced at in spectral mode (hero wavelength sampling). The hero is
+/// sampled uniformly, the other two are spread evenly over the visible range from it, so the
+/// spectral values of a path fit into the three components of a `Color`.
+#[derive(Debug, Clone, Copy)]
+pub struct SampledWavelengths {
+    lambda: [f64; 3],
+    secondary_terminated: bool,
+}
+
+impl SampledWavelengths {
+    pub fn sample(u: f64) -> Self {
+        let range = LAMBDA_MAX - LAMBDA_MIN;
+        let hero = LAMBDA_MIN + u * range;
+        let rotated = |i: f64| LAMBDA_MIN + (hero - LAMBDA_MIN + i * range / 3.) % range;
+
+        Self {
+            lambda: [hero, rotated(1.), rotated(2.)],
+            secondary_terminated: false,
+        }
+    }
+
+    /// Wavelength that wavelength dependent materials scatter the whole path with.
+    pub fn hero(&self) -> f64 {
+        self.lambda[0]
+    }
+
+    /// Drops the secondary wavelengths once the path took a direction only valid for the hero,
+    /// like after refracting through a dispersive dielectric.
+    pub fn terminate_secondary(&mut self) {
+        self.secondary_terminated = true;
+    }
+
+    /// Values of the spectrum upsampled from `rgb` at the sampled wavelengths.
+    pub fn upsample(&self, rgb: Color) -> Vec3 {
+        Vec3::new(
+            rgb_to_spectrum(rgb, self.lambda[0]),
+            rgb_to_spectrum(rgb, self.lambda[1]),
+            rgb_to_spectrum(rgb, self.lambda[2]),
+        )
+    }
+
+    /// Converts the values at the sampled wavelengths into a linear sRGB estimate of the color,
+    /// white balanced so a constant spectrum of 1 is white.
+    pub fn to_rgb(&self, values: Vec3) -> Color {
+        let weights = if self.secondary_terminated {
+            [3., 0., 0.]
+        } else {
+            [1., 1., 1.]
+        };
+
+        // Monte Carlo estimate of the integral of the spectrum against the matching functions,
+        // the uniform pdf cancels against the step of the white point's Riemann sum
+        let xyz = self.lambda.iter().zip(values.to_array()).zip(weights).fold(
+            Vec3::zero(),
+            |xyz, ((&lambda, value), weight)| {
+                xyz + cie_xyz(lambda) * (value * weight / 3. * (LAMBDA_MAX - LAMBDA_MIN))
+            },
+        );
+
+        let rgb = xyz_to_linear_srgb(xyz);
+        let white = white_point();
+        Color::new(
+            rgb.x() / white.x(),
+            rgb.y() / white.y(),
+            rgb.z() / white.z(),
+        )
+    }
+}
+
+// Smooth three band spectrum whose bands add up to one, so white stays exactly white and
+// reflectances in [0, 1] stay physically plausible.
+fn rgb_to_spectrum(rgb: Color, lambda: f64) -> f64 {
+    let blue = 1. - smoothstep(470., 510., lambda);
+    let red = smoothstep(570., 610., lambda);
+    let green = 1. - blue - red;
+    rgb.x() * red + rgb.y() * green + rgb.z() * blue
+}
+
+fn smoothstep(edge0: f64, edge1: f64, x: f64) -> f64 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0., 1.);
+    t * t * (3. - 2. * t)
+}
+
+// Linear sRGB of a constant spectrum of 1, integrated in 1nm steps.
+fn white_point() -> Color {
+    static WHITE: OnceLock<Color> = OnceLock::new();
+    *WHITE.get_or_init(|| {
+        let steps = (LAMBDA_MAX - LAMBDA_MIN) as u32;
+        let xyz = (0..=steps).fold(Vec3::zero(), |xyz, step| {
+            xyz + cie_xyz(LAMBDA_MIN + step as f64)
+        });
+        xyz_to_linear_srgb(xyz)
+    })
+}
+
+/// CIE 1931 color matching functions, multi lobe gaussian fit by Wyman, Sloan and Shirley
+/// ("Simple Analytic Approximations to the CIE XYZ Color Matching Functions").
+pub fn cie_xyz(lambda: f64) -> Vec3 {
+    fn lobe(lambda: f64, mu: f64, sigma_below: f64, sigma_above: f64) -> f64 {
+        let sigma = if lambda < mu {
+            sigma_below
+        } else {
+            sigma_above
+        };
+        let t = (lambda - mu) / sigma;
+        f64::exp(-0.5 * t * t)
+    }
+
+    Vec3::new(
+        1.056 * lobe(lambda, 599.8, 37.9, 31.0) + 0.362 * lobe(lambda, 442.0, 16.0, 26.7)
+            - 0.065 * lobe(lambda, 501.1, 20.4, 26.2),
+        0.821 * lobe(lambda, 568.8, 46.9, 40.5) + 0.286 * lobe(lambda, 530.9, 16.3, 31.1),
+        1.217 * lobe(lambda, 437.0, 11.8, 36.0) + 0.681 * lobe(lambda, 459.0, 26.0, 13.8),
+    )
+}
+
+pub fn xyz_to_linear_srgb(xyz: Vec3) -> Color {
+    Color::new(
+        3.2404542 * xyz.x() - 1.5371385 * xyz.y() - 0.4985314 * xyz.z(),
+        -0.9692660 * xyz.x() + 1.8760108 * xyz.y() + 0.0415560 * xyz.z(),
+        0.0556434 * xyz.x() - 0.2040259 * xyz.y() + 1.0572252 * xyz.z(),
+    )
+}
@@ -0,0 +1,109 @@
+use serde::{Deserialize, Serialize};
+
+use crate::tile::Tile;
+
+/// Renders a left and a right eye next to each other into one image. Each eye gets the
+/// `image_width` and `aspect_ratio` of the camera, so the image is twice as wide or high.
+///
+/// Perspective and orthographic eyes are shifted along the camera's right axis. Fisheyes and
+/// panoramas use omni-directional stereo, where the eyes sit on a circle and every ray starts
+/// from the point on it that is to the side of its direction, fading to a single eye towards
+/// the poles.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug)]
+pub struct StereoConfig {
+    /// Distance between the eyes in world units
+    pub interocular_distance: f64,
+    /// Distance at which both eyes see the same image, nearer objects appear in front of the
+    /// screen. Without it, or with a realistic lens, the eyes look parallel and everything
+    /// appears behind it.
+    #[serde(default)]
+    pub convergence_distance: Option<f64>,
+    #[serde(default)]
+    pub layout: StereoLayout,
+}
+
+/// Where the eyes are placed in the image, left eye first.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum StereoLayout {
+    #[default]
+    SideBySide,
+    TopBottom,
+}
+
+impl StereoConfig {
+    /// Size of the image holding both eyes of size `width` by `height`.
+    pub fn image_size(&self, width: u32, height: u32) -> (u32, u32) {
+        match self.layout {
+            StereoLayout::SideBySide => (2 * width, height),
+            StereoLayout::TopBottom => (width, 2 * height),
+        }
+    }
+
+    /// Parts of the image holding the left and the right eye of size `width` by `height`.
+    pub fn eye_tiles(&self, width: u32, height: u32) -> [Tile; 2] {
+        let (x, y) = match self.layout {
+            StereoLayout::SideBySide => (width, 0),
+            StereoLayout::TopBottom => (0, height),
+        };
+        [
+            Tile {
+                x0: 0,
+                y0: 0,
+                x1: width,
+                y1: height,
+            },
+            Tile {
+                x0: x,
+                y0: y,
+                x1: x + width,
+                y1: y + height,
+            },
+        ]
+    }
+
+    /// Which eye pixel `i`, `j` of the image belongs to, -1 for the left and 1 for the right,
+    /// and its position within the eye.
+    pub fn eye(&self, i: usize, j: usize, width: u32, height: u32) -> (f64, usize, usize) {
+        let (width, height) = (width as usize, height as usize);
+        match self.layout {
+            StereoLayout::SideBySide if i >= width => (1., i - width, j),
+            StereoLayout::TopBottom if j >= height => (1., i, j - height),
+            _ => (-1., i, j),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stereo(layout: StereoLayout) -> StereoConfig {
+        StereoConfig {
+            interocular_distance: 0.064,
+            convergence_distance: None,
+            layout,
+        }
+    }
+
+    #[test]
+    fn every_pixel_belongs_to_the_eye_tile_it_lies_in() {
+        for layout in [StereoLayout::SideBySide, StereoLayout::TopBottom] {
+            let stereo = stereo(layout);
+            let (width, height) = stereo.image_size(6, 4);
+            let [left, right] = stereo.eye_tiles(6, 4);
+            assert_eq!(
+                left.pixel_count() + right.pixel_count(),
+                (width * height) as usize
+            );
+
+            for j in 0..height {
+                for i in 0..width {
+                    let (eye, x, y) = stereo.eye(i as usize, j as usize, 6, 4);
+                    let tile = if eye < 0. { left } else { right };
+                    assert!(tile.contains((i, j)), "{:?} {},{}", layout, i, j);
+                    assert_eq!((x as u32 + tile.x0, y as u32 + tile.y0), (i, j));
+                }
+            }
+        }
+    }
+}
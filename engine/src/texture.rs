@@ -0,0 +1,191 @@
+use std::{
+    cell::RefCell,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use math::vec3::{Color, Point3};
+use serde::{Deserialize, Serialize};
+
+use crate::hittable::hittable::HitRecord;
+
+/// Material parameter that can vary over the surface. A plain value in the scene file is read as
+/// a constant.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(untagged, try_from = "TextureSource<T>")]
+pub enum Texture<T> {
+    Constant(T),
+    /// Solid 3d checker pattern, `scale` is the edge length of a cell in world units
+    Checker {
+        even: T,
+        odd: T,
+        scale: f64,
+    },
+    Image(ImageTexture),
+}
+
+// Images are only loaded once the untagged variants are told apart, otherwise a failed load
+// would be reported as a texture that matches none of them.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TextureSource<T> {
+    Constant(T),
+    Checker { even: T, odd: T, scale: f64 },
+    Image(ImageSource),
+}
+
+impl<T> TryFrom<TextureSource<T>> for Texture<T> {
+    type Error = String;
+
+    fn try_from(source: TextureSource<T>) -> Result<Self, Self::Error> {
+        Ok(match source {
+            TextureSource::Constant(value) => Texture::Constant(value),
+            TextureSource::Checker { even, odd, scale } => Texture::Checker { even, odd, scale },
+            TextureSource::Image(image) => Texture::Image(image.try_into()?),
+        })
+    }
+}
+
+impl<T: Texel> Texture<T> {
+    pub fn value(&self, hit_record: &HitRecord) -> T {
+        self.value_at(hit_record.p, hit_record.u, hit_record.v)
+    }
+
+    /// Value at the point `p` with the surface coordinates `u`, `v`.
+    pub fn value_at(&self, p: Point3, u: f64, v: f64) -> T {
+        match self {
+            Texture::Constant(value) => *value,
+            Texture::Checker { even, odd, scale } => {
+                let p = p / *scale;
+                let cell = p.x().floor() as i64 + p.y().floor() as i64 + p.z().floor() as i64;
+                if cell.rem_euclid(2) == 0 {
+                    *even
+                } else {
+                    *odd
+                }
+            }
+            Texture::Image(image) => T::from_texel(image.sample(u, v)),
+        }
+    }
+}
+
+/// Values an image texture can be read as.
+pub trait Texel: Copy {
+    fn from_texel(texel: Color) -> Self;
+}
+
+impl Texel for f64 {
+    /// Grayscale images, only the red channel is read.
+    fn from_texel(texel: Color) -> Self {
+        texel.x()
+    }
+}
+
+impl Texel for Color {
+    /// Colors are stored gamma corrected, the inverse of the gamma 2 the image is written with.
+    fn from_texel(texel: Color) -> Self {
+        texel * texel
+    }
+}
+
+/// Image mapped onto the surface coordinates of a hit, repeating outside of [0, 1]. The path is
+/// relative to the directory of the scene file.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(try_from = "ImageSource", into = "ImageSource")]
+pub struct ImageTexture {
+    path: String,
+    width: u32,
+    height: u32,
+    // Unaltered channel values in [0, 1], row by row from the top
+    texels: Arc<Vec<Color>>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct ImageSource {
+    image: String,
+}
+
+thread_local! {
+    // Directory relative image paths are resolved against while a scene file is read
+    static IMAGE_DIRECTORY: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+}
+
+/// Runs `read`, which deserializes images, with relative image paths resolved against
+/// `directory` instead of the working directory.
+pub fn with_image_directory<R>(directory: &str, read: impl FnOnce() -> R) -> R {
+    let previous = IMAGE_DIRECTORY.replace(Some(PathBuf::from(directory)));
+    let result = read();
+    IMAGE_DIRECTORY.set(previous);
+    result
+}
+
+impl TryFrom<ImageSource> for ImageTexture {
+    type Error = String;
+
+    fn try_from(source: ImageSource) -> Result<Self, Self::Error> {
+        let path = IMAGE_DIRECTORY.with_borrow(|directory| match directory {
+            Some(directory) => directory.join(&source.image),
+            None => Path::new(&source.image).to_path_buf(),
+        });
+        let image = image::open(&path)
+            .map_err(|error| format!("Failed to load image {}: {}", path.display(), error))?
+            .into_rgb32f();
+
+        Ok(Self {
+            width: image.width(),
+            height: image.height(),
+            texels: Arc::new(
+                image
+                    .pixels()
+                    .map(|pixel| Color::new(pixel[0] as f64, pixel[1] as f64, pixel[2] as f64))
+                    .collect(),
+            ),
+            path: source.image,
+        })
+    }
+}
+
+impl From<ImageTexture> for ImageSource {
+    fn from(texture: ImageTexture) -> Self {
+        Self {
+            image: texture.path,
+        }
+    }
+}
+
+impl ImageTexture {
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Unfiltered channel values of the texel in column `x` and row `y` from the top.
+    pub fn texel(&self, x: u32, y: u32) -> Color {
+        self.texels[y as usize * self.width as usize + x as usize]
+    }
+
+    /// Bilinearly filtered channel values at the surface coordinates `u`, `v`, with `v` pointing
+    /// up the image.
+    pub fn sample(&self, u: f64, v: f64) -> Color {
+        let x = u * self.width as f64 - 0.5;
+        let y = (1. - v) * self.height as f64 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+
+        let texel = |x: f64, y: f64| {
+            let x = (x as i64).rem_euclid(self.width as i64) as usize;
+            let y = (y as i64).rem_euclid(self.height as i64) as usize;
+            self.texels[y * self.width as usize + x]
+        };
+
+        (1. - ty) * ((1. - tx) * texel(x0, y0) + tx * texel(x0 + 1., y0))
+            + ty * ((1. - tx) * texel(x0, y0 + 1.) + tx * texel(x0 + 1., y0 + 1.))
+    }
+}
@@ -0,0 +1,212 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug)]
+pub struct TileConfig {
+    /// Edge length of a square tile in pixels
+    pub size: u32,
+    pub order: TileOrder,
+}
+
+impl Default for TileConfig {
+    fn default() -> Self {
+        Self {
+            size: 32,
+            order: TileOrder::Spiral,
+        }
+    }
+}
+
+/// Order in which tiles are handed to the render threads.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TileOrder {
+    /// Row by row, starting at the top left
+    Scanline,
+    /// Outwards from the center of the image
+    Spiral,
+    /// Along a hilbert curve, keeping consecutive tiles next to each other
+    Hilbert,
+}
+
+/// Rectangular region of the image, `x1` and `y1` are exclusive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Tile {
+    pub x0: u32,
+    pub y0: u32,
+    pub x1: u32,
+    pub y1: u32,
+}
+
+impl Tile {
+    pub fn width(&self) -> u32 {
+        self.x1 - self.x0
+    }
+
+    pub fn height(&self) -> u32 {
+        self.y1 - self.y0
+    }
+
+    pub fn pixel_count(&self) -> usize {
+        self.width() as usize * self.height() as usize
+    }
+
+    pub fn contains(&self, (x, y): (u32, u32)) -> bool {
+        (self.x0..self.x1).contains(&x) && (self.y0..self.y1).contains(&y)
+    }
+
+    /// Row major image indices of the pixels covered by the tile.
+    pub fn pixel_indices(&self, image_width: u32) -> impl Iterator<Item = usize> + '_ {
+        (self.y0..self.y1).flat_map(move |j| {
+            (self.x0..self.x1).map(move |i| j as usize * image_width as usize + i as usize)
+        })
+    }
+}
+
+/// Splits the image into tiles of `config.size` in the configured order.
+pub fn tiles(image_width: u32, image_height: u32, config: &TileConfig) -> Vec<Tile> {
+    let size = config.size.max(1);
+    let tiles_x = image_width.div_ceil(size);
+    let tiles_y = image_height.div_ceil(size);
+
+    let grid = match config.order {
+        TileOrder::Scanline => scanline_order(tiles_x, tiles_y),
+        TileOrder::Spiral => spiral_order(tiles_x, tiles_y),
+        TileOrder::Hilbert => hilbert_order(tiles_x, tiles_y),
+    };
+
+    grid.into_iter()
+        .map(|(tx, ty)| Tile {
+            x0: tx * size,
+            y0: ty * size,
+            x1: ((tx + 1) * size).min(image_width),
+            y1: ((ty + 1) * size).min(image_height),
+        })
+        .collect()
+}
+
+fn scanline_order(tiles_x: u32, tiles_y: u32) -> Vec<(u32, u32)> {
+    (0..tiles_y)
+        .flat_map(|ty| (0..tiles_x).map(move |tx| (tx, ty)))
+        .collect()
+}
+
+fn spiral_order(tiles_x: u32, tiles_y: u32) -> Vec<(u32, u32)> {
+    let total = tiles_x as usize * tiles_y as usize;
+    let mut order = Vec::with_capacity(total);
+
+    // Walk right, down, left, up with the leg length growing every second turn
+    let directions = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+    let (mut x, mut y) = ((tiles_x as i64 - 1) / 2, (tiles_y as i64 - 1) / 2);
+    let mut leg_length = 1;
+    let mut direction = 0;
+
+    while order.len() < total {
+        for _ in 0..2 {
+            let (dx, dy) = directions[direction % 4];
+            for _ in 0..leg_length {
+                if x >= 0 && y >= 0 && x < tiles_x as i64 && y < tiles_y as i64 {
+                    order.push((x as u32, y as u32));
+                }
+                x += dx;
+                y += dy;
+            }
+            direction += 1;
+        }
+        leg_length += 1;
+    }
+
+    order
+}
+
+fn hilbert_order(tiles_x: u32, tiles_y: u32) -> Vec<(u32, u32)> {
+    let n = tiles_x.max(tiles_y).max(1).next_power_of_two();
+
+    (0..(n as u64 * n as u64))
+        .map(|d| hilbert_d2xy(n, d))
+        .filter(|(x, y)| *x < tiles_x && *y < tiles_y)
+        .collect()
+}
+
+// Converts a distance along the hilbert curve filling an n*n grid into grid coordinates.
+fn hilbert_d2xy(n: u32, d: u64) -> (u32, u32) {
+    let (mut x, mut y) = (0u64, 0u64);
+    let mut t = d;
+    let mut s = 1u64;
+
+    while s < n as u64 {
+        let rx = 1 & (t / 2);
+        let ry = 1 & (t ^ rx);
+
+        // Rotate the quadrant
+        if ry == 0 {
+            if rx == 1 {
+                x = s - 1 - x;
+                y = s - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+
+        x += s * rx;
+        y += s * ry;
+        t /= 4;
+        s *= 2;
+    }
+
+    (x as u32, y as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Number of tiles covering every pixel of the image
+    fn coverage(width: u32, height: u32, order: TileOrder) -> Vec<u32> {
+        let config = TileConfig { size: 8, order };
+        let mut covered = vec![0; width as usize * height as usize];
+        for tile in tiles(width, height, &config) {
+            assert!(tile.x0 < tile.x1 && tile.y0 < tile.y1);
+            for idx in tile.pixel_indices(width) {
+                covered[idx] += 1;
+            }
+        }
+        covered
+    }
+
+    #[test]
+    fn every_order_covers_every_pixel_once() {
+        for order in [TileOrder::Scanline, TileOrder::Spiral, TileOrder::Hilbert] {
+            for (width, height) in [(64, 64), (100, 37), (7, 90), (1, 1)] {
+                let covered = coverage(width, height, order);
+                assert!(
+                    covered.iter().all(|&count| count == 1),
+                    "{:?} at {}x{}",
+                    order,
+                    width,
+                    height
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn spiral_starts_in_the_center() {
+        let config = TileConfig {
+            size: 10,
+            order: TileOrder::Spiral,
+        };
+        let first = tiles(50, 30, &config)[0];
+        assert!(first.contains((25, 15)));
+    }
+
+    #[test]
+    fn hilbert_steps_to_neighbouring_tiles() {
+        let config = TileConfig {
+            size: 4,
+            order: TileOrder::Hilbert,
+        };
+        let tiles = tiles(32, 32, &config);
+        for pair in tiles.windows(2) {
+            let distance = pair[0].x0.abs_diff(pair[1].x0) + pair[0].y0.abs_diff(pair[1].y0);
+            assert_eq!(distance, 4);
+        }
+    }
+}
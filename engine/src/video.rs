@@ -0,0 +1,276 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use image::{
+    codecs::gif::{GifEncoder, Repeat},
+    Delay, Frame, RgbImage,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::scene::output::OutputTemplate;
+
+/// Animated image the frames of an animation are assembled into once all of them are rendered.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct VideoConfig {
+    /// Path relative to the directory of the scene file, with the same tokens as the output of
+    /// the frames. The extension picks the format: .gif, .png or .apng, or .webp
+    pub path: OutputTemplate,
+    /// How often the animation plays, 0 repeats it forever
+    #[serde(default)]
+    pub plays: u16,
+    /// Speed of the palette quantization of GIFs from 1, the best palette, to 30, the fastest
+    #[serde(default = "default_quantization_speed")]
+    pub quantization_speed: u8,
+}
+
+fn default_quantization_speed() -> u8 {
+    10
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VideoFormat {
+    /// 256 colors per frame, quantized with NeuQuant
+    Gif,
+    /// Animated PNG, lossless
+    Apng,
+    /// Animated WebP, lossless
+    WebP,
+}
+
+impl VideoConfig {
+    pub fn new(path: OutputTemplate) -> Self {
+        Self {
+            path,
+            plays: 0,
+            quantization_speed: default_quantization_speed(),
+        }
+    }
+}
+
+impl VideoFormat {
+    pub fn from_path(path: &str) -> Result<Self, String> {
+        let extension = Path::new(path)
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase());
+        match extension.as_deref() {
+            Some("gif") => Ok(VideoFormat::Gif),
+            Some("png") | Some("apng") => Ok(VideoFormat::Apng),
+            Some("webp") => Ok(VideoFormat::WebP),
+            _ => Err(format!(
+                "Can't write a video to {}, the extension must be .gif, .png, .apng or .webp",
+                path
+            )),
+        }
+    }
+}
+
+/// Writes `frames` as an animation to `path`, the format follows from its extension. All
+/// frames must have the size of the first one.
+pub fn encode_video(
+    frames: &[RgbImage],
+    frames_per_second: u32,
+    config: &VideoConfig,
+    path: &str,
+) -> Result<(), String> {
+    let format = VideoFormat::from_path(path)?;
+    let Some(first) = frames.first() else {
+        return Err(format!("No frames to write to {}", path));
+    };
+    if frames
+        .iter()
+        .any(|frame| frame.dimensions() != first.dimensions())
+    {
+        return Err(format!(
+            "The frames of {} don't all have the same size",
+            path
+        ));
+    }
+    let frames_per_second = frames_per_second.max(1);
+
+    if let Some(parent) = Path::new(path).parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|error| format!("Failed to create {}: {}", parent.display(), error))?;
+    }
+    let file =
+        File::create(path).map_err(|error| format!("Failed to create {}: {}", path, error))?;
+    let writer = BufWriter::new(file);
+
+    let result = match format {
+        VideoFormat::Gif => encode_gif(writer, frames, frames_per_second, config),
+        VideoFormat::Apng => encode_apng(writer, frames, frames_per_second, config),
+        VideoFormat::WebP => encode_webp(writer, frames, frames_per_second, config),
+    };
+    result.map_err(|error| format!("Failed to write video {}: {}", path, error))
+}
+
+fn encode_gif<W: Write>(
+    writer: W,
+    frames: &[RgbImage],
+    frames_per_second: u32,
+    config: &VideoConfig,
+) -> Result<(), String> {
+    let speed = config.quantization_speed.clamp(1, 30) as i32;
+    let mut encoder = GifEncoder::new_with_speed(writer, speed);
+    // The loop count of a GIF counts the repetitions after the first play, without it the
+    // animation plays once
+    match config.plays {
+        0 => encoder.set_repeat(Repeat::Infinite),
+        1 => Ok(()),
+        plays => encoder.set_repeat(Repeat::Finite(plays - 1)),
+    }
+    .map_err(|error| error.to_string())?;
+
+    let delay = Delay::from_numer_denom_ms(1000, frames_per_second);
+    encoder
+        .encode_frames(frames.iter().map(|frame| {
+            let rgba = image::DynamicImage::ImageRgb8(frame.clone()).into_rgba8();
+            Frame::from_parts(rgba, 0, 0, delay)
+        }))
+        .map_err(|error| error.to_string())
+}
+
+fn encode_apng<W: Write>(
+    writer: W,
+    frames: &[RgbImage],
+    frames_per_second: u32,
+    config: &VideoConfig,
+) -> Result<(), String> {
+    let (width, height) = frames[0].dimensions();
+    let mut encoder = png::Encoder::new(writer, width, height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .set_animated(frames.len() as u32, config.plays as u32)
+        .map_err(|error| error.to_string())?;
+    // Delays are a fraction of 16 bit integers, larger frame rates are only approximated
+    let frames_per_second = frames_per_second.min(u16::MAX as u32) as u16;
+    encoder
+        .set_frame_delay(1, frames_per_second)
+        .map_err(|error| error.to_string())?;
+
+    let mut writer = encoder.write_header().map_err(|error| error.to_string())?;
+    for frame in frames {
+        writer
+            .write_image_data(frame.as_raw())
+            .map_err(|error| error.to_string())?;
+    }
+    writer.finish().map_err(|error| error.to_string())
+}
+
+// Animated WebPs aren't supported by the encoder, so every frame is encoded as a still image
+// whose lossless bitstream is then wrapped into the animation chunks of the extended format.
+fn encode_webp<W: Write>(
+    mut writer: W,
+    frames: &[RgbImage],
+    frames_per_second: u32,
+    config: &VideoConfig,
+) -> Result<(), String> {
+    let (width, height) = frames[0].dimensions();
+    if width > 1 << 14 || height > 1 << 14 {
+        return Err("WebP images are at most 16384 pixels wide and high".to_string());
+    }
+
+    let mut chunks = Vec::new();
+
+    let mut vp8x = vec![0x02, 0, 0, 0];
+    vp8x.extend(u24(width - 1));
+    vp8x.extend(u24(height - 1));
+    write_chunk(&mut chunks, b"VP8X", &vp8x);
+
+    let mut anim = vec![0, 0, 0, 0];
+    anim.extend(config.plays.to_le_bytes());
+    write_chunk(&mut chunks, b"ANIM", &anim);
+
+    // Rounding the end of every frame instead of its duration keeps the total length exact
+    let end_in_ms =
+        |i: usize| (i as u64 * 1000 + frames_per_second as u64 / 2) / frames_per_second as u64;
+    for (i, frame) in frames.iter().enumerate() {
+        let mut still = Vec::new();
+        image_webp::WebPEncoder::new(&mut still)
+            .encode(frame.as_raw(), width, height, image_webp::ColorType::Rgb8)
+            .map_err(|error| error.to_string())?;
+        // Skip the RIFF header to the VP8L chunk
+        let bitstream = still
+            .get(12..)
+            .filter(|chunk| chunk.starts_with(b"VP8L"))
+            .ok_or("Unexpected output of the WebP encoder")?;
+
+        let duration = (end_in_ms(i + 1) - end_in_ms(i)).min((1 << 24) - 1) as u32;
+        let mut anmf = Vec::new();
+        anmf.extend(u24(0));
+        anmf.extend(u24(0));
+        anmf.extend(u24(width - 1));
+        anmf.extend(u24(height - 1));
+        anmf.extend(u24(duration));
+        // Don't blend with the previous frame, which stays in place
+        anmf.push(0x02);
+        anmf.extend(bitstream);
+        write_chunk(&mut chunks, b"ANMF", &anmf);
+    }
+
+    let riff_size = (4 + chunks.len()) as u32;
+    writer
+        .write_all(b"RIFF")
+        .and_then(|_| writer.write_all(&riff_size.to_le_bytes()))
+        .and_then(|_| writer.write_all(b"WEBP"))
+        .and_then(|_| writer.write_all(&chunks))
+        .and_then(|_| writer.flush())
+        .map_err(|error| error.to_string())
+}
+
+fn u24(value: u32) -> [u8; 3] {
+    let bytes = value.to_le_bytes();
+    [bytes[0], bytes[1], bytes[2]]
+}
+
+// RIFF chunks are padded to an even size
+fn write_chunk(out: &mut Vec<u8>, fourcc: &[u8; 4], data: &[u8]) {
+    out.extend(fourcc);
+    out.extend((data.len() as u32).to_le_bytes());
+    out.extend(data);
+    if data.len() % 2 == 1 {
+        out.push(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Cursor, num::NonZeroU16};
+
+    use image::Rgb;
+    use image_webp::{LoopCount, WebPDecoder};
+
+    use super::*;
+
+    #[test]
+    fn webp_round_trip() {
+        let frames: Vec<RgbImage> = (0..3)
+            .map(|i| RgbImage::from_pixel(5, 3, Rgb([i * 80, 10, 200])))
+            .collect();
+        let mut config = VideoConfig::new("video.webp".parse().unwrap());
+        config.plays = 2;
+        let mut data = Vec::new();
+        encode_webp(&mut data, &frames, 24, &config).unwrap();
+
+        let mut decoder = WebPDecoder::new(Cursor::new(data)).unwrap();
+        assert!(decoder.is_animated());
+        assert_eq!(decoder.dimensions(), (5, 3));
+        assert_eq!(decoder.num_frames(), 3);
+        assert_eq!(
+            decoder.loop_count(),
+            LoopCount::Times(NonZeroU16::new(2).unwrap())
+        );
+        // The frame ends are rounded to 42, 83 and 125 ms
+        assert_eq!(decoder.loop_duration(), 125);
+
+        let mut buffer = vec![0; decoder.output_buffer_size().unwrap()];
+        for (frame, expected_duration) in frames.iter().zip([42, 41, 42]) {
+            let duration = decoder.read_frame(&mut buffer).unwrap();
+            assert_eq!(duration, expected_duration);
+            assert_eq!(&buffer, frame.as_raw());
+        }
+    }
+}
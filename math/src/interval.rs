@@ -1,4 +1,4 @@
-use super::{max::max_f64, min::min_f64, random::random_f64};
+use super::{max::max_f64, min::min_f64, random::Pcg32};
 
 pub struct Interval {
     pub min: f64,
@@ -83,7 +83,7 @@ impl Interval {
         }
     }
 
-    pub fn random(&self) -> f64 {
-        self.min + (self.max - self.min) * random_f64()
+    pub fn random(&self, rng: &mut Pcg32) -> f64 {
+        self.min + (self.max - self.min) * rng.next_f64()
     }
 }
@@ -3,5 +3,6 @@ pub mod constants;
 pub mod interval;
 pub mod max;
 pub mod min;
+pub mod onb;
 pub mod random;
 pub mod vec3;
@@ -0,0 +1,42 @@
+use super::vec3::Vec3;
+
+/// Orthonormal basis around a normal, used to move directions into and out of a local shading
+/// frame where the normal is the z axis.
+#[derive(Debug, Clone, Copy)]
+pub struct Onb {
+    pub u: Vec3,
+    pub v: Vec3,
+    pub w: Vec3,
+}
+
+impl Onb {
+    /// Builds a basis with `w` along the normal (Duff et al., "Building an Orthonormal Basis,
+    /// Revisited"). The normal is assumed to have unit length.
+    pub fn from_normal(normal: Vec3) -> Self {
+        let sign = 1f64.copysign(normal.z());
+        let a = -1.0 / (sign + normal.z());
+        let b = normal.x() * normal.y() * a;
+
+        Self {
+            u: Vec3::new(
+                1.0 + sign * normal.x() * normal.x() * a,
+                sign * b,
+                -sign * normal.x(),
+            ),
+            v: Vec3::new(b, sign + normal.y() * normal.y() * a, -normal.y()),
+            w: normal,
+        }
+    }
+
+    pub fn to_local(&self, direction: Vec3) -> Vec3 {
+        Vec3::new(
+            direction.dot(&self.u),
+            direction.dot(&self.v),
+            direction.dot(&self.w),
+        )
+    }
+
+    pub fn to_world(&self, direction: Vec3) -> Vec3 {
+        direction.x() * self.u + direction.y() * self.v + direction.z() * self.w
+    }
+}
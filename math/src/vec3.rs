@@ -3,10 +3,10 @@ use std::ops::{Add, Div, Mul, Sub};
 
 use super::interval::Interval;
 use super::min::min_f64;
-use super::random::random_f64;
+use super::random::Pcg32;
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Copy, Deserialize, Serialize)]
+#[derive(Clone, Copy, PartialEq, Deserialize, Serialize)]
 pub struct Vec3 {
     x: f64,
     y: f64,
@@ -14,10 +14,10 @@ pub struct Vec3 {
 }
 
 impl Vec3 {
-    pub fn new(x: f64, y: f64, z: f64) -> Vec3 {
+    pub const fn new(x: f64, y: f64, z: f64) -> Vec3 {
         Vec3 { x, y, z }
     }
-    pub fn zero() -> Vec3 {
+    pub const fn zero() -> Vec3 {
         Vec3::new(0.0, 0.0, 0.0)
     }
     pub fn one() -> Vec3 {
@@ -66,8 +66,8 @@ impl Vec3 {
         )
     }
 
-    pub fn random() -> Self {
-        Vec3::new(random_f64(), random_f64(), random_f64())
+    pub fn random(rng: &mut Pcg32) -> Self {
+        Vec3::new(rng.next_f64(), rng.next_f64(), rng.next_f64())
     }
 
     pub fn inverted(&self) -> Self {
@@ -78,13 +78,17 @@ impl Vec3 {
         }
     }
 
-    pub fn random_interval(interval: Interval) -> Self {
-        Vec3::new(interval.random(), interval.random(), interval.random())
+    pub fn random_interval(interval: Interval, rng: &mut Pcg32) -> Self {
+        Vec3::new(
+            interval.random(rng),
+            interval.random(rng),
+            interval.random(rng),
+        )
     }
 
-    pub fn random_unit_vector() -> Self {
+    pub fn random_unit_vector(rng: &mut Pcg32) -> Self {
         loop {
-            let p = Vec3::random_interval(Interval::new(-1.0, 1.0));
+            let p = Vec3::random_interval(Interval::new(-1.0, 1.0), rng);
             let lensq = p.length_squared();
             // Use a more practical threshold to avoid floating-point precision issues
             if lensq > 1e-8 && lensq <= 1.0 {
@@ -93,18 +97,44 @@ impl Vec3 {
         }
     }
 
-    pub fn random_in_unit_disk() -> Self {
+    pub fn random_in_unit_disk(rng: &mut Pcg32) -> Self {
         loop {
             let interval = Interval::new(-1.0, 1.0);
-            let p = Vec3::new(interval.random(), interval.random(), 0.0);
+            let p = Vec3::new(interval.random(rng), interval.random(rng), 0.0);
             if p.length_squared() < 1.0 {
                 return p;
             }
         }
     }
 
-    pub fn random_on_hemisphere(normal: &Vec3) -> Self {
-        let on_unit_sphere = Vec3::random_unit_vector();
+    /// Maps a point of the unit square uniformly onto the unit sphere.
+    pub fn unit_vector_from_sample(u: f64, v: f64) -> Self {
+        let z = 1.0 - 2.0 * u;
+        let r = f64::sqrt(f64::max(0.0, 1.0 - z * z));
+        let phi = 2.0 * std::f64::consts::PI * v;
+        Vec3::new(r * phi.cos(), r * phi.sin(), z)
+    }
+
+    /// Maps a point of the unit square uniformly onto the unit disk, keeping neighbouring points
+    /// close to each other (Shirley-Chiu concentric mapping).
+    pub fn in_unit_disk_from_sample(u: f64, v: f64) -> Self {
+        let a = 2.0 * u - 1.0;
+        let b = 2.0 * v - 1.0;
+        if a == 0.0 && b == 0.0 {
+            return Vec3::zero();
+        }
+
+        let quarter_pi = std::f64::consts::FRAC_PI_4;
+        let (r, theta) = if a.abs() > b.abs() {
+            (a, quarter_pi * (b / a))
+        } else {
+            (b, 2.0 * quarter_pi - quarter_pi * (a / b))
+        };
+        Vec3::new(r * theta.cos(), r * theta.sin(), 0.0)
+    }
+
+    pub fn random_on_hemisphere(normal: &Vec3, rng: &mut Pcg32) -> Self {
+        let on_unit_sphere = Vec3::random_unit_vector(rng);
         if on_unit_sphere.dot(normal) > 0.0 {
             on_unit_sphere
         } else {
@@ -114,18 +144,22 @@ impl Vec3 {
 
     pub fn is_near_zero(&self) -> bool {
         let s = 1e-8;
-        return self.x.abs() < s && self.y.abs() < s && self.z.abs() < s;
+        self.x.abs() < s && self.y.abs() < s && self.z.abs() < s
     }
 
     pub fn reflect(&self, normal: &Vec3) -> Self {
-        return *self - 2. * self.dot(normal) * *normal;
+        *self - 2. * self.dot(normal) * *normal
     }
 
     pub fn refract(&self, normal: &Vec3, etai_over_etat: f64) -> Self {
         let cos_theta = min_f64(self.inverted().dot(normal), 1.0);
         let r_out_perp = etai_over_etat * (*self + cos_theta * *normal);
         let r_out_parallel = (-f64::sqrt(f64::abs(1.0 - r_out_perp.length_squared()))) * *normal;
-        return r_out_perp + r_out_parallel;
+        r_out_perp + r_out_parallel
+    }
+
+    pub fn max_component(&self) -> f64 {
+        self.x.max(self.y).max(self.z)
     }
 
     pub fn to_array(&self) -> [f64; 3] {
@@ -260,10 +294,15 @@ pub fn linear_to_gamma_corrected(linear_component: f64) -> f64 {
         return linear_component.sqrt();
     }
 
-    return 0.;
+    0.
 }
 
 impl Color {
+    /// Relative luminance of a linear rgb color (Rec. 709 weights).
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.x() + 0.7152 * self.y() + 0.0722 * self.z()
+    }
+
     pub fn to_pixel(&self) -> [u8; 3] {
         let mut r = self.x();
         let mut g = self.y();
@@ -279,7 +318,7 @@ impl Color {
         let gbyte = (256. * INTENSITY.clamp(g)) as u8;
         let bbyte = (256. * INTENSITY.clamp(b)) as u8;
 
-        return [rbyte, gbyte, bbyte];
+        [rbyte, gbyte, bbyte]
     }
 }
 